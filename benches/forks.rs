@@ -0,0 +1,68 @@
+// Breadth first search over every sequence of 0/1 inputs, forking the machine at each one,
+// to compare forking paged machines with copying their memory outright.
+//
+//   cargo bench --bench forks
+
+use aoc2019_rust::intcode::memory::memory_footprint;
+use aoc2019_rust::intcode::Intcode;
+use std::time::{Duration, Instant};
+
+const DEPTH: usize = 12;
+
+fn main() {
+    // arb 1000; in [rb+0]; out [rb+0]; arb 100; jt 1, 2
+    // Each input lands 100 cells past the last one, so forks keep dirtying new pages.
+    let program = vec![109, 1000, 203, 0, 204, 0, 109, 100, 1105, 1, 2];
+    let mut root = Intcode::new(program, true);
+    root.progress_program().unwrap();
+
+    let mut frontier = vec![root];
+    let mut forks = 0;
+    let mut clone_time = Duration::default();
+    for _ in 0..DEPTH {
+        let mut next = Vec::with_capacity(frontier.len() * 2);
+        for ic in &frontier {
+            for input in 0..2 {
+                let start = Instant::now();
+                let mut fork = ic.clone();
+                clone_time += start.elapsed();
+                forks += 1;
+
+                fork.queue_input(input);
+                fork.progress_program().unwrap();
+                assert_eq!(fork.dequeue_output(), Some(input));
+                next.push(fork);
+            }
+        }
+        frontier = next;
+    }
+
+    let cells = frontier[0].memory_size();
+    let flat = vec![0i64; cells];
+    let start = Instant::now();
+    for _ in 0..forks {
+        std::hint::black_box(flat.clone());
+    }
+    let flat_time = start.elapsed();
+
+    let mb = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+    println!(
+        "{} forks, {} machines at depth {}",
+        forks,
+        frontier.len(),
+        DEPTH
+    );
+    println!(
+        "clone: {:?} total, {:?} per fork ({:?} per fork copying {} cells)",
+        clone_time,
+        clone_time / forks,
+        flat_time / forks,
+        cells
+    );
+    println!(
+        "memory: {:.1} MB for every machine at depth {} ({:.1} MB copying)",
+        mb(memory_footprint(&frontier)),
+        DEPTH,
+        mb(frontier.len() * cells * std::mem::size_of::<i64>())
+    );
+}
@@ -0,0 +1,25 @@
+#![warn(clippy::all)]
+
+use aoc2019_rust::intcode::runner;
+use std::io;
+use std::process;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = runner::parse_args(&args).unwrap_or_else(|e| {
+        eprintln!("{}\n\n{}", e, runner::USAGE);
+        process::exit(2);
+    });
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    if let Err(e) = runner::run(
+        &options,
+        &mut stdin.lock(),
+        &mut stdout.lock(),
+        &mut io::stderr(),
+    ) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
@@ -55,8 +55,8 @@ fn trace_wires(input: Vec<String>) -> (i32, i32) {
         maps.push(steps);
     }
 
-    let mut least_man = std::i32::MAX;
-    let mut least_steps = std::i32::MAX;
+    let mut least_man = i32::MAX;
+    let mut least_steps = i32::MAX;
     for (x, y) in sets[0].intersection(&sets[1]) {
         let manhattan = x.abs() + y.abs();
         if manhattan < least_man {
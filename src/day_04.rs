@@ -59,9 +59,9 @@ fn digits(n: u32) -> Vec<u32> {
 
 #[test]
 fn can_validate_a_password() {
-    assert_eq!(password_matches(111_111, false), true);
-    assert_eq!(password_matches(223_450, false), false);
-    assert_eq!(password_matches(123_789, false), false);
-    assert_eq!(password_matches(111_222, true), false);
-    assert_eq!(password_matches(112_222, true), true);
+    assert!(password_matches(111_111, false));
+    assert!(!password_matches(223_450, false));
+    assert!(!password_matches(123_789, false));
+    assert!(!password_matches(111_222, true));
+    assert!(password_matches(112_222, true));
 }
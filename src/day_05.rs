@@ -1,31 +1,17 @@
+use crate::intcode::diagnostics::{diagnose, Convention};
+use crate::intcode::loader::load_program;
 use crate::intcode::Intcode;
-use std::fs;
 
 pub fn solve() {
-    let filename = "res/day_05.txt";
-    let input = fs::read_to_string(filename)
-        .unwrap_or_else(|_| panic!("Could not read file: {}", filename));
-    let memory: Vec<i64> = input
-        .trim()
-        .split(',')
-        .map(|s| s.parse().unwrap())
-        .collect();
+    let memory = load_program("res/day_05.txt").unwrap_or_else(|e| panic!("{}", e));
 
-    let mut ic = Intcode::new(memory.clone(), false);
-    ic.queue_input(1);
-    ic.progress_program()
-        .unwrap_or_else(|_| panic!("Could not progress program!"));
-    while let Some(n) = ic.dequeue_output() {
-        if n > 0 {
-            println!("{}", n);
-        }
+    let report = diagnose(memory.clone(), 1, Convention::Test).unwrap_or_else(|e| panic!("{}", e));
+    if !report.passed() {
+        panic!("{}", report);
     }
+    println!("{}", report.code.unwrap());
 
-    ic = Intcode::new(memory, false);
-    ic.queue_input(5);
-    ic.progress_program()
-        .unwrap_or_else(|_| panic!("Could not progress program!"));
-    while let Some(n) = ic.dequeue_output() {
-        println!("{}", n);
-    }
+    Intcode::run_iter(memory, vec![5])
+        .map(|o| o.unwrap_or_else(|e| panic!("{}", e)))
+        .for_each(|n| println!("{}", n));
 }
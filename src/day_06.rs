@@ -33,7 +33,7 @@ fn trace_orbits(
         if let Some(path) = cache.get(planet) {
             path.clone()
         } else {
-            let result = vec![
+            let result = [
                 vec![(*orbiting).to_string()],
                 trace_orbits(orbiting, orbital_data, cache),
             ]
@@ -69,7 +69,7 @@ fn path_between(
     let mut result_b = path_b[b_i..].to_vec();
     result_b.reverse();
 
-    vec![result_a, vec![path_a[a_i - 1].to_string()], result_b].concat()
+    [result_a, vec![path_a[a_i - 1].to_string()], result_b].concat()
 }
 
 #[test]
@@ -124,7 +124,7 @@ fn can_get_shortest_path_between_planets() {
     let mut cache: HashMap<String, Vec<String>> = HashMap::new();
     let chart = get_orbital_data(&test_input);
 
-    let result: Vec<String> = vec!["K", "J", "E", "D", "I"]
+    let result: Vec<String> = ["K", "J", "E", "D", "I"]
         .iter()
         .map(|s| (*s).to_string())
         .collect();
@@ -45,7 +45,7 @@ impl<'a> IntoIterator for &'a ImageBuffer {
 
     fn into_iter(self) -> <Self as std::iter::IntoIterator>::IntoIter {
         ImageBufferIterator {
-            buffer: &self,
+            buffer: self,
             current_layer: 0,
         }
     }
@@ -122,7 +122,7 @@ pub fn solve() {
 
     let part_one = most_filled_layer_counts.1 * most_filled_layer_counts.2;
 
-    println!("{}\n{}", part_one, ib.to_string());
+    println!("{}\n{}", part_one, ib);
 }
 
 #[test]
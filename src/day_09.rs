@@ -1,30 +1,17 @@
+use crate::intcode::diagnostics::{diagnose, Convention};
+use crate::intcode::loader::load_program;
 use crate::intcode::Intcode;
-use std::fs;
 
 pub fn solve() {
-    let filename = "res/day_09.txt";
-    let input = fs::read_to_string(filename)
-        .unwrap_or_else(|_| panic!("Could not read file: {}", filename));
-    let memory: Vec<i64> = input
-        .trim()
-        .split(',')
-        .map(|s| {
-            s.parse()
-                .unwrap_or_else(|_| panic!("Could not parse {} into a i64", s))
-        })
-        .collect();
+    let memory = load_program("res/day_09.txt").unwrap_or_else(|e| panic!("{}", e));
 
-    let mut ic = Intcode::new(memory.clone(), true);
-    ic.queue_input(1);
-    ic.progress_program().unwrap();
-    while let Some(o) = ic.dequeue_output() {
-        println!("{}", o);
+    let report = diagnose(memory.clone(), 1, Convention::Boost).unwrap_or_else(|e| panic!("{}", e));
+    if !report.passed() {
+        panic!("{}", report);
     }
+    println!("{}", report.code.unwrap());
 
-    ic = Intcode::new(memory, true);
-    ic.queue_input(2);
-    ic.progress_program().unwrap();
-    while let Some(o) = ic.dequeue_output() {
-        println!("{}", o);
-    }
+    Intcode::run_iter(memory, vec![2])
+        .map(|o| o.unwrap_or_else(|e| panic!("{}", e)))
+        .for_each(|o| println!("{}", o));
 }
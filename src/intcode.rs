@@ -1,39 +1,108 @@
-use std::collections::VecDeque;
-
-#[derive(PartialEq, Clone, Copy)]
+use colored::*;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::ops::Range;
+use std::rc::Rc;
+
+pub mod assembler;
+pub mod call_stack;
+pub mod conformance;
+pub mod coverage;
+pub mod debug_server;
+pub mod diagnostics;
+pub mod disassembler;
+pub mod display;
+pub mod extensions;
+pub mod instruction;
+pub mod linker;
+pub mod linter;
+pub mod loader;
+pub mod loops;
+pub mod memory;
+pub mod observer;
+pub mod optimizer;
+pub mod outputs;
+pub mod pages;
+pub mod peripherals;
+pub mod runner;
+pub mod search;
+pub mod session;
+pub mod stdlib;
+pub mod symbolic;
+pub mod trace;
+pub mod validator;
+
+pub use call_stack::CallStack;
+pub use coverage::Coverage;
+pub use debug_server::DebugServer;
+pub use extensions::Extension;
+pub use instruction::{Instruction, Mode, Param};
+pub use observer::Observer;
+pub use pages::PagedMemory;
+pub use peripherals::Device;
+pub use session::{IoEvent, Session};
+pub use trace::Trace;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
 pub enum IntcodeState {
     NotStarted,
     Running,
     PollingInput,
     Done,
+    // The machine's state repeated exactly, so it will never halt
+    Looping { start: usize, period: u64 },
 }
 
 const MEMORY_SIZE: u32 = 4096;
 
+type MappedDevice = (Range<usize>, Rc<RefCell<dyn Device>>);
+
 pub struct Intcode {
-    memory: Vec<i64>,
+    memory: PagedMemory,
+    initial: PagedMemory,
     ip: usize,
     rb: isize,
     state: IntcodeState,
     input_queue: VecDeque<i64>,
     output_queue: VecDeque<i64>,
+    extensions: HashMap<u8, Rc<RefCell<dyn Extension>>>,
+    devices: Vec<MappedDevice>,
+    observers: Vec<Rc<RefCell<dyn Observer>>>,
+    cycles: u64,
+    recording: Option<RefCell<session::Recording>>,
+    loop_detector: Option<loops::LoopDetector>,
+    call_stack: Option<RefCell<CallStack>>,
+    dump_on_error: bool,
+    strict: bool,
 }
 
 impl Intcode {
     pub fn new(memory: Vec<i64>, pad_memory: bool) -> Intcode {
         let padding: i32 = MEMORY_SIZE as i32 - memory.len() as i32;
         let memory: Vec<i64> = if pad_memory && padding > 0 {
-            vec![memory, vec![0; padding as usize]].concat()
+            [memory, vec![0; padding as usize]].concat()
         } else {
             memory
         };
+        let memory = PagedMemory::from(memory);
         Intcode {
+            initial: memory.clone(),
             memory,
             ip: 0,
             rb: 0,
             state: IntcodeState::NotStarted,
             input_queue: VecDeque::new(),
             output_queue: VecDeque::new(),
+            extensions: HashMap::new(),
+            devices: Vec::new(),
+            observers: Vec::new(),
+            cycles: 0,
+            recording: None,
+            loop_detector: None,
+            call_stack: None,
+            dump_on_error: true,
+            strict: false,
         }
     }
 
@@ -51,6 +120,114 @@ impl Intcode {
         self.output_queue.pop_front()
     }
 
+    pub fn register_extension(
+        &mut self,
+        opcode: u8,
+        extension: Rc<RefCell<dyn Extension>>,
+    ) -> Result<(), String> {
+        if instruction::mnemonic(i64::from(opcode)).is_some() {
+            return Err(format!("Cannot override built-in opcode: {}", opcode));
+        }
+        if opcode >= 100 {
+            return Err(format!("Extension opcodes must be below 100: {}", opcode));
+        }
+        self.extensions.insert(opcode, extension);
+        Ok(())
+    }
+
+    pub fn map_device(
+        &mut self,
+        range: Range<usize>,
+        device: Rc<RefCell<dyn Device>>,
+    ) -> Result<(), String> {
+        if range.start >= range.end {
+            return Err(format!(
+                "Cannot map a device to an empty range: {:?}",
+                range
+            ));
+        }
+        if let Some(size) = device.borrow().size() {
+            if range.len() > size {
+                return Err(format!(
+                    "Cannot map a device with {} cells to {:?}",
+                    size, range
+                ));
+            }
+        }
+        if let Some((mapped, _)) = self
+            .devices
+            .iter()
+            .find(|(r, _)| r.start < range.end && range.start < r.end)
+        {
+            return Err(format!(
+                "Device range {:?} overlaps already mapped range {:?}",
+                range, mapped
+            ));
+        }
+        self.devices.push((range, device));
+        Ok(())
+    }
+
+    pub fn add_observer(&mut self, observer: Rc<RefCell<dyn Observer>>) {
+        self.observers.push(observer);
+    }
+
+    // Number of instructions executed so far
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn device_at(&self, addr: usize) -> Option<(usize, &Rc<RefCell<dyn Device>>)> {
+        self.devices
+            .iter()
+            .find(|(r, _)| r.contains(&addr))
+            .map(|(r, d)| (addr - r.start, d))
+    }
+
+    fn load(&self, addr: usize) -> i64 {
+        match self.device_at(addr) {
+            Some((offset, device)) => device.borrow_mut().read(offset),
+            None => self.memory[addr],
+        }
+    }
+
+    fn store(&mut self, addr: usize, val: i64) {
+        match self.device_at(addr) {
+            Some((offset, device)) => device.borrow_mut().write(offset, val),
+            None => self.memory[addr] = val,
+        }
+    }
+
+    pub fn ip(&self) -> usize {
+        self.ip
+    }
+
+    pub fn jump_to(&mut self, addr: usize) {
+        self.ip = addr;
+    }
+
+    pub fn advance_ip(&mut self) {
+        self.ip += 1;
+    }
+
+    pub fn take_input(&mut self) -> Option<i64> {
+        let i = self.input_queue.pop_front()?;
+        self.notify(|observer| observer.input_consumed(i));
+        if let Some(detector) = self.loop_detector.as_mut() {
+            detector.reset();
+        }
+        Some(i)
+    }
+
+    pub fn wait_for_input(&mut self) {
+        self.state = IntcodeState::PollingInput;
+    }
+
+    pub fn push_output(&mut self, o: i64) {
+        self.notify(|observer| observer.output_produced(o));
+        self.output_queue.push_back(o);
+    }
+
     fn parse_opcode(opcode: u32) -> (u8, Vec<u8>) {
         let mut opcode = opcode;
         let op = (opcode % 100) as u8;
@@ -66,27 +243,86 @@ impl Intcode {
         (op, modes)
     }
 
-    fn read_next_ins_param(&mut self, mode: u8) -> i64 {
+    fn in_bounds(&self, addr: usize) -> bool {
+        addr < self.memory.len() || self.device_at(addr).is_some()
+    }
+
+    // Where a position or relative mode parameter points, checked so that a negative or
+    // wrapped address fails the machine instead of panicking
+    fn resolve(&self, param: i64, mode: Mode) -> Result<usize, String> {
+        let (addr, mode) = if mode == Mode::Relative {
+            (
+                (self.rb as i64).checked_add(param),
+                format!("relative mode, {} from rb {}", param, self.rb),
+            )
+        } else {
+            (Some(param), format!("position mode, rb {}", self.rb))
+        };
+        match addr {
+            Some(addr) if addr >= 0 && self.in_bounds(addr as usize) => Ok(addr as usize),
+            Some(addr) => Err(format!("Invalid address {} ({})", addr, mode)),
+            None => Err(format!("Address overflows ({})", mode)),
+        }
+    }
+
+    fn next_word(&mut self) -> Result<i64, String> {
         self.ip += 1;
-        let mut result = self.memory[self.ip];
-        if mode == 0 {
-            result = self.memory[result as usize];
+        if !self.in_bounds(self.ip) {
+            return Err(format!(
+                "Instruction runs past the end of memory at {}",
+                self.ip
+            ));
         }
-        if mode == 2 {
-            result = self.memory[(self.rb + result as isize) as usize];
+        Ok(self.load(self.ip))
+    }
+
+    fn read_param(&mut self, param: Param) -> Result<i64, String> {
+        if param.mode == Mode::Immediate {
+            return Ok(param.value);
         }
-        result
+        let addr = self.resolve(param.value, param.mode)?;
+        let value = self.load(addr);
+        self.notify(|observer| observer.memory_read(addr, value));
+        Ok(value)
     }
 
-    fn write_next_ins_param(&mut self, val: i64, mode: u8) {
-        self.ip += 1;
-        let dest_addr = self.memory[self.ip] as usize;
-        if mode == 0 {
-            self.memory[dest_addr] = val;
+    // Where a write to the parameter goes, None for immediate mode where it goes nowhere
+    fn destination(&self, param: Param) -> Result<Option<usize>, String> {
+        match param.mode {
+            Mode::Immediate => Ok(None),
+            mode => self.resolve(param.value, mode).map(Some),
         }
-        if mode == 2 {
-            self.memory[(self.rb + dest_addr as isize) as usize] = val;
+    }
+
+    fn store_at(&mut self, dest: Option<usize>, val: i64) {
+        if let Some(addr) = dest {
+            self.store(addr, val);
+            self.notify(|observer| observer.memory_write(addr, val));
+        }
+    }
+
+    fn write_param(&mut self, param: Param, val: i64) -> Result<(), String> {
+        let dest = self.destination(param)?;
+        self.store_at(dest, val);
+        Ok(())
+    }
+
+    pub fn read_next_ins_param(&mut self, mode: u8) -> Result<i64, String> {
+        let value = self.next_word()?;
+        self.read_param(Param::new(Mode::lenient(mode), value))
+    }
+
+    pub fn write_next_ins_param(&mut self, val: i64, mode: u8) -> Result<(), String> {
+        let dest = self.next_word()?;
+        self.write_param(Param::new(Mode::lenient(mode), dest), val)
+    }
+
+    fn jump_target(&mut self, param: Param) -> Result<usize, String> {
+        let target = self.read_param(param)?;
+        if target < 0 {
+            return Err(format!("Invalid jump target {}", target));
         }
+        Ok(target as usize)
     }
 
     fn compute_next_op(&mut self) -> Result<&IntcodeState, String> {
@@ -94,7 +330,7 @@ impl Intcode {
             self.state = IntcodeState::Running;
         }
 
-        if self.state == IntcodeState::Done {
+        if let IntcodeState::Done | IntcodeState::Looping { .. } = self.state {
             return Ok(&self.state);
         }
 
@@ -113,102 +349,215 @@ impl Intcode {
             ));
         }
 
-        let opcode = self.memory[self.ip] as u32;
-        let (op, modes) = Intcode::parse_opcode(opcode);
+        let start = self.ip;
+        let opcode = self.load(start);
+        let builtin = opcode >= 0 && instruction::mnemonic(opcode % 100).is_some();
+        if self.strict && builtin {
+            validator::check_modes(opcode)
+                .map_err(|e| format!("Invalid instruction at {}: {}", start, e))?;
+        }
+
+        let waiting = opcode % 100 == 3 && self.input_queue.is_empty();
+        if !waiting {
+            self.cycles += 1;
+            self.notify(|observer| observer.before_instruction(self, start));
+        }
 
-        match op {
-            1 => {
-                let left = self.read_next_ins_param(modes[0]);
-                let right = self.read_next_ins_param(modes[1]);
-                self.write_next_ins_param(left + right, modes[2]);
-                self.ip += 1;
+        let result = if builtin {
+            Instruction::decode_lenient(self, start)
+                .and_then(|instruction| self.execute(instruction, start))
+                .map_err(|e| format!("Instruction at {} failed: {}", start, e))
+        } else {
+            self.execute_extension(opcode)
+        };
+        if let Err(e) = result {
+            // Leave ip on the failed instruction so the machine can be inspected
+            self.ip = start;
+            return Err(e);
+        }
+        if waiting && self.state == IntcodeState::PollingInput {
+            return Ok(&self.state);
+        }
+
+        // Devices only see instructions that finished, not ones waiting for input or failing
+        for (_, device) in &self.devices {
+            device.borrow_mut().tick();
+        }
+        self.notify(|observer| observer.after_instruction(self, start));
+        if self.state == IntcodeState::Done {
+            self.notify(|observer| observer.halted(self));
+        } else if self.loop_detector.is_some() {
+            self.check_for_loop()?;
+        }
+        Ok(&self.state)
+    }
+
+    fn execute(&mut self, instruction: Instruction, start: usize) -> Result<(), String> {
+        use Instruction::*;
+        let mut next = start + instruction.size();
+        match instruction {
+            Add(left, right, dest) => {
+                let (left, right) = (self.read_param(left)?, self.read_param(right)?);
+                let sum = left
+                    .checked_add(right)
+                    .ok_or_else(|| format!("Addition overflows ({} + {})", left, right))?;
+                self.write_param(dest, sum)?;
             }
-            2 => {
-                let left = self.read_next_ins_param(modes[0]);
-                let right = self.read_next_ins_param(modes[1]);
-                self.write_next_ins_param(left * right, modes[2]);
-                self.ip += 1;
+            Mul(left, right, dest) => {
+                let (left, right) = (self.read_param(left)?, self.read_param(right)?);
+                let product = left
+                    .checked_mul(right)
+                    .ok_or_else(|| format!("Multiplication overflows ({} * {})", left, right))?;
+                self.write_param(dest, product)?;
             }
-            3 => {
+            In(dest) => {
                 if self.input_queue.is_empty() {
                     self.state = IntcodeState::PollingInput;
-                    return Ok(&self.state);
+                    return Ok(());
                 }
-                let i = self.input_queue.pop_front().unwrap();
-                self.write_next_ins_param(i, modes[0]);
-                self.ip += 1;
+                // Only take the input once it has somewhere to go, so a failed instruction
+                // can be retried
+                let dest = self.destination(dest)?;
+                let i = self.take_input().unwrap();
+                self.store_at(dest, i);
             }
-            4 => {
-                let o = self.read_next_ins_param(modes[0]);
-                self.output_queue.push_back(o);
-                self.ip += 1;
+            Out(param) => {
+                let o = self.read_param(param)?;
+                self.push_output(o);
             }
-            5 => {
-                let cond = self.read_next_ins_param(modes[0]) != 0;
-                let jump = self.read_next_ins_param(modes[1]) as usize;
-                if cond {
-                    self.ip = jump;
-                } else {
-                    self.ip += 1;
+            JumpIfTrue(cond, target) | JumpIfFalse(cond, target) => {
+                let taken = (self.read_param(cond)? != 0) == matches!(instruction, JumpIfTrue(..));
+                if taken {
+                    next = self.jump_target(target)?;
                 }
+                self.notify(|observer| observer.branch(start, taken));
             }
-            6 => {
-                let cond = self.read_next_ins_param(modes[0]) == 0;
-                let jump = self.read_next_ins_param(modes[1]) as usize;
-                if cond {
-                    self.ip = jump;
-                } else {
-                    self.ip += 1;
-                }
+            LessThan(first, snd, dest) => {
+                let result = i64::from(self.read_param(first)? < self.read_param(snd)?);
+                self.write_param(dest, result)?;
             }
-            7 => {
-                let first = self.read_next_ins_param(modes[0]);
-                let snd = self.read_next_ins_param(modes[1]);
-                let result = if first < snd { 1 } else { 0 };
-                self.write_next_ins_param(result, modes[2]);
-                self.ip += 1;
+            Equals(first, snd, dest) => {
+                let result = i64::from(self.read_param(first)? == self.read_param(snd)?);
+                self.write_param(dest, result)?;
             }
-            8 => {
-                let first = self.read_next_ins_param(modes[0]);
-                let snd = self.read_next_ins_param(modes[1]);
-                let result = if first == snd { 1 } else { 0 };
-                self.write_next_ins_param(result, modes[2]);
-                self.ip += 1;
+            AdjustBase(param) => {
+                let offset = self.read_param(param)?;
+                self.rb = (self.rb as i64).checked_add(offset).ok_or_else(|| {
+                    format!("Relative base overflows ({} from rb {})", offset, self.rb)
+                })? as isize;
             }
-            9 => {
-                self.rb += self.read_next_ins_param(modes[0]) as isize;
-                self.ip += 1;
-            }
-            99 => {
+            Halt => {
                 self.state = IntcodeState::Done;
-                return Ok(&self.state);
+                next = start;
             }
-            _ => return Err(format!("Unsupported opcode: {}", opcode)),
         }
+        self.ip = next;
+        Ok(())
+    }
 
-        Ok(&self.state)
+    fn execute_extension(&mut self, opcode: i64) -> Result<(), String> {
+        // Opcodes past u32 would lose their modes when parsed, so they can't be extensions
+        let unsupported = || format!("Unsupported opcode: {}", opcode);
+        let (op, modes) = match u32::try_from(opcode) {
+            Ok(opcode) => Intcode::parse_opcode(opcode),
+            Err(_) => return Err(unsupported()),
+        };
+        let extension = match self.extensions.get(&op) {
+            Some(extension) => Rc::clone(extension),
+            None => return Err(unsupported()),
+        };
+        let mut extension = extension.borrow_mut();
+        extension.execute(self, &modes)
+    }
+
+    // Executes a single instruction, unless the machine is done or still waiting on input
+    pub fn step(&mut self) -> Result<IntcodeState, String> {
+        self.compute_next_op().copied()
+    }
+
+    // Whether progress_program prints the machine's state to stderr when it fails (it does
+    // by default)
+    pub fn set_dump_on_error(&mut self, dump: bool) {
+        self.dump_on_error = dump;
     }
 
     pub fn progress_program(&mut self) -> Result<(), String> {
-        while self.state != IntcodeState::Done {
-            let state = self.compute_next_op()?;
-            if *state == IntcodeState::PollingInput {
-                break;
+        loop {
+            match self.compute_next_op() {
+                Ok(IntcodeState::Running) => {}
+                Ok(_) => return Ok(()),
+                Err(e) => {
+                    if self.dump_on_error {
+                        eprintln!("{}\n{}", e.red().bold(), self);
+                    }
+                    return Err(e);
+                }
             }
         }
-        Ok(())
     }
 }
 
+// Runs machines in a ring where each one's outputs feed the next, with the last feeding
+// back into the first, and returns the last machine's outputs from the round it halts in.
+pub fn run_ring(machines: &mut [Intcode], input: i64) -> Result<Vec<i64>, String> {
+    let last = machines
+        .len()
+        .checked_sub(1)
+        .ok_or("Cannot run an empty ring")?;
+    let mut last_outputs: VecDeque<i64> = VecDeque::new();
+    last_outputs.push_back(input);
+    loop {
+        let mut progressed = false;
+        for (i, ic) in machines.iter_mut().enumerate() {
+            while let Some(n) = last_outputs.pop_front() {
+                ic.queue_input(n);
+            }
+
+            ic.progress_program()?;
+            if let IntcodeState::Looping { start, period } = ic.get_state() {
+                return Err(format!(
+                    "Machine {} is stuck in a loop at {} with period {}",
+                    i, start, period
+                ));
+            }
+
+            while let Some(n) = ic.dequeue_output() {
+                last_outputs.push_back(n);
+                progressed = true;
+            }
+
+            if i == last && ic.get_state() == IntcodeState::Done {
+                return Ok(last_outputs.into_iter().collect());
+            }
+        }
+        if !progressed {
+            return Err("Ring deadlocked with every machine waiting for input".to_string());
+        }
+    }
+}
+
+// A clone gets its own memory, registers, queues, recording and call stack, but shares the
+// original's extensions, devices and observers. Forked machines drive the same peripherals, so map fresh devices on
+// a clone that needs its own.
 impl Clone for Intcode {
     fn clone(&self) -> Self {
         Intcode {
             memory: self.memory.clone(),
+            initial: self.initial.clone(),
             ip: self.ip,
             rb: self.rb,
             state: self.state,
             input_queue: self.input_queue.clone(),
             output_queue: self.output_queue.clone(),
+            extensions: self.extensions.clone(),
+            devices: self.devices.clone(),
+            observers: self.observers.clone(),
+            cycles: self.cycles,
+            recording: self.recording.clone(),
+            loop_detector: self.loop_detector.clone(),
+            call_stack: self.call_stack.clone(),
+            dump_on_error: self.dump_on_error,
+            strict: self.strict,
         }
     }
 }
@@ -220,82 +569,59 @@ fn can_parse_opcode_and_modes() {
 }
 
 #[test]
-fn can_run_intcode_programs() {
-    let mut ic = Intcode::new([1, 0, 0, 0, 99].to_vec(), false);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.memory, [2, 0, 0, 0, 99].to_vec());
-
-    ic = Intcode::new([2, 3, 0, 3, 99].to_vec(), false);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.memory, [2, 3, 0, 6, 99].to_vec());
-
-    ic = Intcode::new([2, 4, 4, 5, 99, 0].to_vec(), false);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.memory, [2, 4, 4, 5, 99, 9801].to_vec());
-
-    ic = Intcode::new([1, 1, 1, 4, 99, 5, 6, 0, 99].to_vec(), false);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.memory, [30, 1, 1, 4, 2, 5, 6, 0, 99].to_vec());
-}
-
-#[test]
-fn intcode_can_do_io() {
-    let mut ic = Intcode::new([3, 0, 4, 0, 99].to_vec(), false);
-    ic.queue_input(1337);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.output_queue.len(), 1);
-    assert_eq!(ic.dequeue_output(), Some(1337));
-}
-
-#[test]
-fn intcode_handles_jumps_and_conditionals() {
-    let memory = vec![
-        3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0, 0,
-        1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4, 20,
-        1105, 1, 46, 98, 99,
+fn bad_instructions_fail_the_machine_without_moving_it() {
+    let failures = vec![
+        // out [-1]
+        (
+            vec![4, -1, 99],
+            "Instruction at 0 failed: Invalid address -1 (position mode, rb 0)",
+        ),
+        // arb 2; add [rb-5], 1, [0]
+        (
+            vec![109, 2, 1201, -5, 1, 0, 99],
+            "Instruction at 2 failed: Invalid address -3 (relative mode, -5 from rb 2)",
+        ),
+        // arb 1; out [rb+i64::MAX]
+        (
+            vec![109, 1, 204, i64::MAX, 99],
+            "Instruction at 2 failed: Address overflows (relative mode, 9223372036854775807 from rb 1)",
+        ),
+        // jt 1, -4
+        (
+            vec![1105, 1, -4, 99],
+            "Instruction at 0 failed: Invalid jump target -4",
+        ),
+        // add [0], [0], ...
+        (
+            vec![1, 0, 0],
+            "Instruction at 0 failed: Instruction runs past the end of memory at 3",
+        ),
+        // add i64::MAX, 1, [0]
+        (
+            vec![1101, i64::MAX, 1, 0, 99],
+            "Instruction at 0 failed: Addition overflows (9223372036854775807 + 1)",
+        ),
+        // mul i64::MIN, -1, [0]
+        (
+            vec![1102, i64::MIN, -1, 0, 99],
+            "Instruction at 0 failed: Multiplication overflows (-9223372036854775808 * -1)",
+        ),
     ];
+    for (program, error) in failures {
+        let mut ic = Intcode::new(program.clone(), false);
+        assert_eq!(ic.progress_program(), Err(error.to_string()));
+        assert!(error.starts_with(&format!("Instruction at {} ", ic.ip())));
+        assert_eq!(ic.memory.to_vec(), program);
+    }
 
-    let mut ic = Intcode::new(memory.clone(), false);
-    ic.queue_input(7);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.output_queue.len(), 1);
-    assert_eq!(ic.dequeue_output(), Some(999));
-
-    ic = Intcode::new(memory.clone(), false);
-    ic.queue_input(8);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.output_queue.len(), 1);
-    assert_eq!(ic.dequeue_output(), Some(1000));
-
-    ic = Intcode::new(memory, false);
-    ic.queue_input(9);
-    ic.progress_program().unwrap();
-    assert_eq!(ic.output_queue.len(), 1);
-    assert_eq!(ic.dequeue_output(), Some(1001));
-}
+    // The input isn't lost when it can't be stored
+    let mut ic = Intcode::new(vec![3, -1, 99], false);
+    ic.queue_input(5);
+    assert!(ic.progress_program().is_err());
+    assert_eq!(ic.take_input(), Some(5));
 
-#[test]
-fn intcode_handles_mode_2_relative_positions_and_memory_padding() {
-    let memory = vec![
-        109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
-    ];
-    let mut ic = Intcode::new(memory.clone(), true);
-    ic.progress_program().unwrap();
-    let result: Vec<i64> = ic.output_queue.into_iter().collect();
-    assert_eq!(result, memory);
-}
-
-#[test]
-fn intcode_handles_large_numbers() {
-    let mut memory = vec![1102, 34_915_192, 34_915_192, 7, 4, 7, 99, 0];
-    let mut ic = Intcode::new(memory.clone(), true);
-    ic.progress_program().unwrap();
-    let result = ic.dequeue_output().unwrap();
-    assert_eq!(result.to_string().len(), 16);
-
-    memory = vec![104, 1_125_899_906_842_624, 99];
-    ic = Intcode::new(memory, true);
-    ic.progress_program().unwrap();
-    let result = ic.dequeue_output().unwrap();
-    assert_eq!(result, 1_125_899_906_842_624);
+    // Nor does a jump that isn't taken care where it would have gone
+    let outputs: Result<Vec<i64>, String> =
+        Intcode::run_iter(vec![1105, 0, -1, 104, 7, 99], vec![]).collect();
+    assert_eq!(outputs, Ok(vec![7]));
 }
@@ -0,0 +1,270 @@
+use super::instruction::{opcode, param_count, Instruction, Mode, Param};
+use super::linker::{Import, Object};
+use super::validator::check_modes;
+use std::collections::BTreeMap;
+
+// Assembles the disassembler's syntax, so anything it prints assembles back to the same
+// cells. On top of that:
+//
+//     label:  add [label+1], 2, [rb-3]   # labels can be used wherever a number can
+//             .data 1, -2, label         # raw cells
+//             .export label              # lets other modules refer to a label
+//             .import name               # a label from another module
+//
+// Labels stand for addresses, so every use of one becomes a relocation (or an import) in the
+// object. Relative mode offsets have to be plain numbers.
+
+// A cell that refers to a symbol, filled in once every label is known
+struct Reference {
+    cell: usize,
+    symbol: String,
+    line: usize,
+}
+
+struct Assembler {
+    object: Object,
+    labels: BTreeMap<String, usize>,
+    // Each imported symbol and the line it was imported on
+    imports: BTreeMap<String, usize>,
+    exports: Vec<(String, usize)>,
+    references: Vec<Reference>,
+}
+
+fn is_symbol(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Assembler {
+    // A number, a label, or a label plus or minus a number, for the cell at cell
+    fn value(&mut self, text: &str, cell: usize, line: usize) -> Result<i64, String> {
+        if let Ok(n) = text.parse() {
+            return Ok(n);
+        }
+        let (symbol, offset) = match text.find(['+', '-']) {
+            Some(i) => {
+                let offset = text[i..].trim_start_matches('+').replace(' ', "");
+                let offset = offset
+                    .parse()
+                    .map_err(|_| format!("Invalid offset {:?} on line {}", &text[i..], line))?;
+                (text[..i].trim(), offset)
+            }
+            None => (text, 0),
+        };
+        if !is_symbol(symbol) {
+            return Err(format!("Invalid value {:?} on line {}", text, line));
+        }
+        self.references.push(Reference {
+            cell,
+            symbol: symbol.to_string(),
+            line,
+        });
+        Ok(offset)
+    }
+
+    fn operand(&mut self, text: &str, cell: usize, line: usize) -> Result<Param, String> {
+        let (mode, value) = if let Some(inner) = text.strip_prefix('[') {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or_else(|| format!("Missing ']' in {:?} on line {}", text, line))?
+                .trim();
+            match inner.strip_prefix("rb") {
+                Some(offset) => {
+                    let offset = offset.trim().trim_start_matches('+').replace(' ', "");
+                    let offset = offset.parse().map_err(|_| {
+                        format!("Invalid relative offset {:?} on line {}", text, line)
+                    })?;
+                    (Mode::Relative, offset)
+                }
+                None => (Mode::Position, self.value(inner, cell, line)?),
+            }
+        } else {
+            (Mode::Immediate, self.value(text, cell, line)?)
+        };
+        Ok(Param::new(mode, value))
+    }
+
+    fn instruction(&mut self, name: &str, operands: &str, line: usize) -> Result<(), String> {
+        let op = opcode(name)
+            .ok_or_else(|| format!("Unknown instruction {:?} on line {}", name, line))?;
+        let operands: Vec<&str> = if operands.is_empty() {
+            vec![]
+        } else {
+            operands.split(',').map(str::trim).collect()
+        };
+        if operands.len() != param_count(op) {
+            return Err(format!(
+                "{} takes {} operands, not {}, on line {}",
+                name,
+                param_count(op),
+                operands.len(),
+                line
+            ));
+        }
+
+        let start = self.object.code.len();
+        let mut params = Vec::new();
+        for (i, operand) in operands.iter().enumerate() {
+            params.push(self.operand(operand, start + 1 + i, line)?);
+        }
+        let cells = Instruction::new(op, &params).unwrap().encode();
+        check_modes(cells[0]).map_err(|e| format!("{} on line {}", e, line))?;
+        self.object.code.extend(cells);
+        Ok(())
+    }
+
+    fn directive(&mut self, name: &str, args: &str, line: usize) -> Result<(), String> {
+        let args = args.split(',').map(str::trim).filter(|arg| !arg.is_empty());
+        match name {
+            ".data" => {
+                for arg in args {
+                    let value = self.value(arg, self.object.code.len(), line)?;
+                    self.object.code.push(value);
+                }
+            }
+            ".export" | ".import" => {
+                for arg in args {
+                    if !is_symbol(arg) {
+                        return Err(format!("Invalid symbol {:?} on line {}", arg, line));
+                    }
+                    if name == ".export" {
+                        self.exports.push((arg.to_string(), line));
+                    } else {
+                        self.imports.entry(arg.to_string()).or_insert(line);
+                    }
+                }
+            }
+            _ => return Err(format!("Unknown directive {:?} on line {}", name, line)),
+        }
+        Ok(())
+    }
+
+    fn line(&mut self, text: &str, line: usize) -> Result<(), String> {
+        let mut text = text.split('#').next().unwrap_or("").trim();
+        if let Some(colon) = text.find(':') {
+            let label = text[..colon].trim();
+            if !is_symbol(label) {
+                return Err(format!("Invalid label {:?} on line {}", label, line));
+            }
+            let addr = self.object.code.len();
+            if self.labels.insert(label.to_string(), addr).is_some() {
+                return Err(format!("Label {} defined twice, on line {}", label, line));
+            }
+            text = text[colon + 1..].trim();
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+        let (name, rest) = match text.find(char::is_whitespace) {
+            Some(i) => (&text[..i], text[i..].trim()),
+            None => (text, ""),
+        };
+        if name.starts_with('.') {
+            self.directive(name, rest, line)
+        } else {
+            self.instruction(name, rest, line)
+        }
+    }
+
+    fn finish(mut self) -> Result<Object, String> {
+        if let Some((symbol, line)) = self
+            .imports
+            .iter()
+            .find(|(symbol, _)| self.labels.contains_key(*symbol))
+        {
+            return Err(format!(
+                "Imported symbol {} is also a label, on line {}",
+                symbol, line
+            ));
+        }
+        for reference in self.references {
+            if let Some(&addr) = self.labels.get(&reference.symbol) {
+                self.object.code[reference.cell] += addr as i64;
+                self.object.relocations.push(reference.cell);
+            } else if self.imports.contains_key(&reference.symbol) {
+                self.object.imports.push(Import {
+                    cell: reference.cell,
+                    symbol: reference.symbol,
+                });
+            } else {
+                return Err(format!(
+                    "Undefined symbol {} on line {}",
+                    reference.symbol, reference.line
+                ));
+            }
+        }
+        for (symbol, line) in self.exports {
+            let addr = *self.labels.get(&symbol).ok_or_else(|| {
+                format!(
+                    "Exported symbol {} is not defined, on line {}",
+                    symbol, line
+                )
+            })?;
+            self.object.exports.insert(symbol, addr);
+        }
+        Ok(self.object)
+    }
+}
+
+// Assembles one module. Line numbers in errors count from 1.
+pub fn assemble(name: &str, source: &str) -> Result<Object, String> {
+    let mut assembler = Assembler {
+        object: Object {
+            name: name.to_string(),
+            ..Object::default()
+        },
+        labels: BTreeMap::new(),
+        imports: BTreeMap::new(),
+        exports: Vec::new(),
+        references: Vec::new(),
+    };
+    for (i, line) in source.lines().enumerate() {
+        assembler
+            .line(line, i + 1)
+            .map_err(|e| format!("{}: {}", name, e))?;
+    }
+    assembler.finish().map_err(|e| format!("{}: {}", name, e))
+}
+
+#[test]
+fn can_assemble_what_the_disassembler_prints() {
+    use super::disassembler::disassemble;
+
+    for day in &["02", "05", "09"] {
+        let program = super::loader::load_program(&format!("res/day_{}.txt", day)).unwrap();
+        let source: Vec<String> = disassemble(&program)
+            .into_iter()
+            .map(|line| line.text)
+            .collect();
+        let object = assemble("day", &source.join("\n")).unwrap();
+        assert_eq!(object.code, program, "day {}", day);
+        assert!(object.relocations.is_empty());
+    }
+
+    let object = assemble("loop", "top: add [top+4], -1, [rb-2]\njt 1, top").unwrap();
+    assert_eq!(object.code, vec![21001, 4, -1, -2, 1105, 1, 0]);
+    assert_eq!(object.relocations, vec![1, 6]);
+
+    let errors = [
+        ("add 1, 2", "add takes 3 operands, not 2, on line 1"),
+        (
+            "\nadd 1, 2, 3",
+            "Immediate mode write to parameter 3 of 11101 on line 2",
+        ),
+        ("jt 1, nowhere", "Undefined symbol nowhere on line 1"),
+        ("mov 1, 2", "Unknown instruction \"mov\" on line 1"),
+        ("a: hlt\na: hlt", "Label a defined twice, on line 2"),
+        (".export b", "Exported symbol b is not defined, on line 1"),
+        (
+            "hlt\n.import c\nc: hlt",
+            "Imported symbol c is also a label, on line 2",
+        ),
+        ("out [rb+x]", "Invalid relative offset \"[rb+x]\" on line 1"),
+    ];
+    for (source, error) in &errors {
+        assert_eq!(assemble("m", source), Err(format!("m: {}", error)));
+    }
+}
@@ -0,0 +1,144 @@
+use super::{Intcode, Observer, PagedMemory};
+use std::cell::{Ref, RefCell};
+
+// Intcode has no call instruction, so calls are inferred from the usual relative base
+// convention: the caller saves the address after its jump in a cell just above rb and jumps
+// to the function, which moves rb up past its frame and eventually moves it back down and
+// jumps to the saved address. Any taken jump with its own return address sitting in one of
+// the first few cells above rb counts as a call (once rb has moved off 0, since before
+// that the cells above it are the program itself), and any jump to the return address of a
+// frame on the stack returns from it (and from anything it called that never returned).
+
+// How many cells above rb a return address can be saved in
+const RETURN_SLOTS: isize = 4;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub call_site: usize,
+    pub entry: usize,
+    pub return_addr: usize,
+    pub rb: isize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    frames: Vec<Frame>,
+    // Whether the instruction that's executing is a jump that was taken
+    jumped: bool,
+}
+
+impl CallStack {
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    fn record_jump(&mut self, from: usize, to: usize, memory: &PagedMemory, rb: isize) {
+        if let Some(depth) = self.frames.iter().rposition(|f| f.return_addr == to) {
+            self.frames.truncate(depth);
+            return;
+        }
+        let return_addr = from + 3;
+        let saved = rb > 0
+            && (0..RETURN_SLOTS)
+                .filter_map(|offset| memory.get((rb + offset) as usize))
+                .any(|cell| cell == return_addr as i64);
+        if saved {
+            self.frames.push(Frame {
+                call_site: from,
+                entry: to,
+                return_addr,
+                rb,
+            });
+        }
+    }
+
+    // Innermost frame first, starting from ip
+    pub fn backtrace(&self, ip: usize) -> Vec<String> {
+        let function = |depth: usize| match depth {
+            0 => "main".to_string(),
+            _ => format!("fn@{}", self.frames[depth - 1].entry),
+        };
+        let mut lines = vec![format!("#0 {} in {}", ip, function(self.frames.len()))];
+        for (i, frame) in self.frames.iter().enumerate().rev() {
+            lines.push(format!(
+                "#{} {} in {}",
+                self.frames.len() - i,
+                frame.call_site,
+                function(i)
+            ));
+        }
+        lines
+    }
+}
+
+// Jumps are only known to have been taken once they've finished, which is when ip has
+// their target
+impl Observer for CallStack {
+    fn branch(&mut self, _ip: usize, taken: bool) {
+        self.jumped = taken;
+    }
+
+    fn after_instruction(&mut self, ic: &Intcode, ip: usize) {
+        if self.jumped {
+            self.jumped = false;
+            self.record_jump(ip, ic.ip, &ic.memory, ic.rb);
+        }
+    }
+}
+
+impl Intcode {
+    pub fn enable_call_stack(&mut self) {
+        self.call_stack = Some(RefCell::new(CallStack::default()));
+    }
+
+    pub fn call_stack(&self) -> Option<Ref<'_, CallStack>> {
+        self.call_stack.as_ref().map(RefCell::borrow)
+    }
+
+    pub fn backtrace(&self) -> Option<Vec<String>> {
+        self.call_stack().map(|stack| stack.backtrace(self.ip))
+    }
+}
+
+#[test]
+fn can_reconstruct_the_call_stack() {
+    // f(n) calls f(n - 1) until n is 0, then runs whatever is at 40
+    #[rustfmt::skip]
+    let mut program = vec![
+        109, 100,            //  0: arb 100
+        21101, 0, 2, 1,      //  2: add 0, 2, [rb+1]       n = 2
+        21101, 0, 13, 0,     //  6: add 0, 13, [rb+0]      return to 13
+        1105, 1, 15,         // 10: jt 1, 15               call f
+        99, 0,               // 13: hlt
+        109, 2,              // 15: arb 2                  f:
+        1206, -1, 40,        // 17: jf [rb-1], 40
+        21201, -1, -1, 1,    // 20: add [rb-1], -1, [rb+1]
+        21101, 0, 31, 0,     // 24: add 0, 31, [rb+0]      return to 31
+        1105, 1, 15,         // 28: jt 1, 15               call f
+        109, -2,             // 31: arb -2
+        2105, 1, 0,          // 33: jt 1, [rb+0]           return
+        0, 0, 0, 0,
+        98,                  // 40: an unsupported opcode
+    ];
+
+    let mut ic = Intcode::new(program.clone(), true);
+    ic.enable_call_stack();
+    assert!(ic.progress_program().is_err());
+    assert_eq!(
+        ic.backtrace().unwrap(),
+        vec![
+            "#0 40 in fn@15",
+            "#1 28 in fn@15",
+            "#2 28 in fn@15",
+            "#3 10 in main"
+        ]
+    );
+    assert_eq!(ic.call_stack().unwrap().frames()[0].rb, 100);
+
+    // 40: jt 1, 31 so the deepest call returns normally instead
+    program.splice(40..41, vec![1105, 1, 31]);
+    let mut ic = Intcode::new(program, true);
+    ic.enable_call_stack();
+    ic.progress_program().unwrap();
+    assert_eq!(ic.backtrace().unwrap(), vec!["#0 13 in main"]);
+}
@@ -0,0 +1,245 @@
+use super::loader::{load_program, parse_text};
+use super::{run_ring, Intcode, IntcodeState, Trace};
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+// Test vectors live in plain text files, one section per vector:
+//
+//   # comments start with a hash
+//   [name of the vector]
+//   program = 3,0,4,0,99        (or program_file = res/day_05.txt)
+//   pad = true                  (pad memory like day 9, defaults to false)
+//   input = 1337                (optional)
+//   output = 1337               (optional, but checked exactly when present)
+//   memory = 1337,0,4,0,99      (optional, the expected leading memory cells)
+//   ring = 4,3,2,1,0            (optional, run one machine per seed wired in a ring)
+//
+// Lists ending in a comma continue on the next line. With ring the first input is fed to
+// the first machine and output is compared against what the last machine emitted in the
+// round it halted in.
+#[derive(Debug, Default)]
+pub struct TestVector {
+    pub name: String,
+    pub source: String,
+    pub program: Vec<i64>,
+    pub pad: bool,
+    pub input: Vec<i64>,
+    pub output: Option<Vec<i64>>,
+    pub memory: Option<Vec<i64>>,
+    pub ring: Option<Vec<i64>>,
+}
+
+const TRACE_LENGTH: usize = 8;
+
+pub fn parse_vectors(input: &str, source: &str) -> Result<Vec<TestVector>, String> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        match lines.last_mut() {
+            Some((_, previous)) if previous.ends_with(',') => previous.push_str(line),
+            _ => lines.push((i + 1, line.to_string())),
+        }
+    }
+
+    let mut vectors: Vec<TestVector> = Vec::new();
+    for (number, line) in &lines {
+        let location = format!("{}:{}", source, number);
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            vectors.push(TestVector {
+                name: line[1..line.len() - 1].trim().to_string(),
+                source: location,
+                ..TestVector::default()
+            });
+            continue;
+        }
+        let vector = vectors
+            .last_mut()
+            .ok_or_else(|| format!("{}: expected a [name] before any fields", location))?;
+        let (key, value) = match line.find('=') {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim()),
+            None => return Err(format!("{}: expected key = value", location)),
+        };
+        let values = || parse_text(value).map_err(|e| format!("{}: {}", location, e));
+        match key {
+            "program" => vector.program = values()?,
+            "program_file" => vector.program = load_program(value)?,
+            "pad" => {
+                vector.pad = value
+                    .parse()
+                    .map_err(|_| format!("{}: pad must be true or false", location))?
+            }
+            "input" => vector.input = values()?,
+            "output" => vector.output = Some(values()?),
+            "memory" => vector.memory = Some(values()?),
+            "ring" => vector.ring = Some(values()?),
+            _ => return Err(format!("{}: unknown field {}", location, key)),
+        }
+    }
+    for vector in &vectors {
+        if vector.program.is_empty() {
+            return Err(format!("{}: vector has no program", vector.source));
+        }
+    }
+    Ok(vectors)
+}
+
+pub fn load_vectors(dir: &str) -> Result<Vec<TestVector>, String> {
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .map_err(|e| format!("Could not read directory {}: {}", dir, e))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+
+    let mut vectors = Vec::new();
+    for path in paths {
+        let source = path.display().to_string();
+        let input = fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read file {}: {}", source, e))?;
+        vectors.append(&mut parse_vectors(&input, &source)?);
+    }
+    Ok(vectors)
+}
+
+fn mismatch(vector: &TestVector, what: &str, expected: &[i64], actual: &[i64]) -> String {
+    let index = expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .unwrap_or_else(|| expected.len().min(actual.len()));
+    format!(
+        "{} [{}]: {} differs at index {}\n    expected: {:?}\n    actual:   {:?}",
+        vector.source, vector.name, what, index, expected, actual
+    )
+}
+
+fn with_trace(message: String, trace: &RefCell<Trace>) -> String {
+    format!("{}\n  last instructions:\n{}", message, trace.borrow())
+}
+
+pub fn run_vector(vector: &TestVector) -> Result<(), String> {
+    let new_machine = || {
+        let trace = Rc::new(RefCell::new(Trace::new(TRACE_LENGTH)));
+        let mut ic = Intcode::new(vector.program.clone(), vector.pad);
+        ic.set_strict(true);
+        // Failures are reported with the trace instead
+        ic.set_dump_on_error(false);
+        ic.add_observer(trace.clone());
+        (ic, trace)
+    };
+
+    let (ic, trace, output) = match &vector.ring {
+        Some(seeds) => {
+            let (mut machines, mut traces): (Vec<Intcode>, Vec<_>) = seeds
+                .iter()
+                .map(|seed| {
+                    let (mut ic, trace) = new_machine();
+                    ic.queue_input(*seed);
+                    (ic, trace)
+                })
+                .unzip();
+            let input = *vector.input.first().unwrap_or(&0);
+            let result = run_ring(&mut machines, input);
+            let (ic, trace) = (machines.pop().unwrap(), traces.pop().unwrap());
+            let output = result.map_err(|e| {
+                with_trace(
+                    format!("{} [{}]: {}", vector.source, vector.name, e),
+                    &trace,
+                )
+            })?;
+            (ic, trace, output)
+        }
+        None => {
+            let (mut ic, trace) = new_machine();
+            for n in &vector.input {
+                ic.queue_input(*n);
+            }
+            if let Err(e) = ic.progress_program() {
+                let message = format!("{} [{}]: {}", vector.source, vector.name, e);
+                return Err(with_trace(message, &trace));
+            }
+            if ic.get_state() != IntcodeState::Done {
+                let message = format!(
+                    "{} [{}]: program is still waiting for input at {}",
+                    vector.source, vector.name, ic.ip
+                );
+                return Err(with_trace(message, &trace));
+            }
+            let output: Vec<i64> = ic.output_queue.iter().copied().collect();
+            (ic, trace, output)
+        }
+    };
+
+    if let Some(expected) = &vector.output {
+        if *expected != output {
+            return Err(with_trace(
+                mismatch(vector, "output", expected, &output),
+                &trace,
+            ));
+        }
+    }
+    if let Some(expected) = &vector.memory {
+        let actual = &ic.memory.range(0..expected.len().min(ic.memory.len()));
+        if expected.as_slice() != actual {
+            return Err(with_trace(
+                mismatch(vector, "memory", expected, actual),
+                &trace,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Runs every vector and returns how many passed, or every failure joined together
+pub fn run_suite(dir: &str) -> Result<usize, String> {
+    let vectors = load_vectors(dir)?;
+    let failures: Vec<String> = vectors
+        .iter()
+        .filter_map(|vector| run_vector(vector).err())
+        .collect();
+    if failures.is_empty() {
+        Ok(vectors.len())
+    } else {
+        Err(format!(
+            "{} of {} vectors failed\n\n{}",
+            failures.len(),
+            vectors.len(),
+            failures.join("\n\n")
+        ))
+    }
+}
+
+#[test]
+fn intcode_passes_the_conformance_vectors() {
+    match run_suite("res/intcode_vectors") {
+        Ok(passed) => assert!(passed > 0),
+        Err(report) => panic!("{}", report),
+    }
+}
+
+#[test]
+fn reports_mismatches_with_a_trace() {
+    let vectors = parse_vectors(
+        "[wrong]\nprogram = 1,0,0,0,99\nmemory = 2,0,0,1,99\n",
+        "inline",
+    )
+    .unwrap();
+    let report = run_vector(&vectors[0]).unwrap_err();
+    assert_eq!(
+        report,
+        "inline:1 [wrong]: memory differs at index 3\n    expected: [2, 0, 0, 1, 99]\n    actual:   [2, 0, 0, 0, 99]\n  last instructions:\n         0: 1,0,0,0\n         4: 99\n"
+    );
+
+    assert_eq!(
+        parse_vectors("program = 99\n", "inline").unwrap_err(),
+        "inline:1: expected a [name] before any fields"
+    );
+    assert_eq!(
+        parse_vectors("[bad]\nprogram = 1,x\n", "inline").unwrap_err(),
+        "inline:2: Invalid number \"x\" at byte offset 2 (token 1)"
+    );
+}
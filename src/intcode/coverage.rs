@@ -0,0 +1,181 @@
+use super::disassembler::{data, Line};
+use super::{Instruction, Intcode, Observer};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BranchCount {
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Coverage {
+    pub executed: BTreeSet<usize>,
+    pub reads: BTreeSet<usize>,
+    pub writes: BTreeSet<usize>,
+    pub branches: BTreeMap<usize, BranchCount>,
+}
+
+impl Coverage {
+    pub fn record_branch(&mut self, addr: usize, taken: bool) {
+        let count = self.branches.entry(addr).or_default();
+        if taken {
+            count.taken += 1;
+        } else {
+            count.not_taken += 1;
+        }
+    }
+
+    // Combine the coverage of several runs of the same program
+    pub fn merge(&mut self, other: &Coverage) {
+        self.executed.extend(&other.executed);
+        self.reads.extend(&other.reads);
+        self.writes.extend(&other.writes);
+        for (addr, count) in &other.branches {
+            let total = self.branches.entry(*addr).or_default();
+            total.taken += count.taken;
+            total.not_taken += count.not_taken;
+        }
+    }
+
+    // Addresses executed as an instruction are disassembled, cells only touched as data are
+    // shown as data, and anything never reached is disassembled if it decodes.
+    pub fn report(&self, program: &[i64]) -> String {
+        let mut lines = Vec::new();
+        let mut instructions = 0;
+        let mut directions = 0;
+        let mut addr = 0;
+        while addr < program.len() {
+            let executed = self.executed.contains(&addr);
+            let touched = self.reads.contains(&addr) || self.writes.contains(&addr);
+            let instruction = Instruction::decode(program, addr)
+                .ok()
+                .filter(|_| executed || !touched);
+            let line = match instruction {
+                Some(instruction) => Line {
+                    addr,
+                    len: instruction.size(),
+                    text: instruction.to_string(),
+                },
+                None => data(program, addr),
+            };
+            let jump = matches!(
+                instruction,
+                Some(Instruction::JumpIfTrue(..)) | Some(Instruction::JumpIfFalse(..))
+            );
+            if instruction.is_some() {
+                instructions += 1;
+            }
+
+            let marker = if executed { '*' } else { ' ' };
+            let access = match (self.reads.contains(&addr), self.writes.contains(&addr)) {
+                (true, true) => "rw",
+                (true, false) => "r ",
+                (false, true) => " w",
+                (false, false) => "  ",
+            };
+            let mut text = format!("{} {} {:>6}: {}", marker, access, addr, line.text);
+            if let Some(count) = self.branches.get(&addr) {
+                text += &format!("    (taken {}, not taken {})", count.taken, count.not_taken);
+            } else if executed && jump {
+                text += "    (never decided)";
+            }
+            if jump {
+                directions += 2;
+            }
+            lines.push(text);
+            addr += line.len;
+        }
+
+        let executed = self.executed.iter().filter(|a| **a < program.len()).count();
+        let covered_directions: usize = self
+            .branches
+            .values()
+            .map(|c| (c.taken > 0) as usize + (c.not_taken > 0) as usize)
+            .sum();
+        lines.push(format!(
+            "{} of {} instructions executed, {} of {} branch directions taken",
+            executed, instructions, covered_directions, directions
+        ));
+        lines.join("\n")
+    }
+}
+
+impl Observer for Coverage {
+    fn before_instruction(&mut self, _ic: &Intcode, ip: usize) {
+        self.executed.insert(ip);
+    }
+
+    fn branch(&mut self, ip: usize, taken: bool) {
+        self.record_branch(ip, taken);
+    }
+
+    fn memory_read(&mut self, addr: usize, _value: i64) {
+        self.reads.insert(addr);
+    }
+
+    fn memory_write(&mut self, addr: usize, _value: i64) {
+        self.writes.insert(addr);
+    }
+}
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::rc::Rc;
+
+#[test]
+fn can_track_coverage_across_runs() {
+    // in [9]; jt [9], 7; out 0; hlt   (with the input cell at 9)
+    let program = vec![3, 9, 1005, 9, 7, 104, 0, 99, 0, 0];
+    let mut total = Coverage::default();
+    for input in &[0, 5, 6] {
+        let coverage = Rc::new(RefCell::new(Coverage::default()));
+        let mut ic = Intcode::new(program.clone(), false);
+        ic.add_observer(coverage.clone());
+        ic.queue_input(*input);
+        ic.progress_program().unwrap();
+        total.merge(&coverage.borrow());
+    }
+    assert_eq!(total.executed, [0, 2, 5, 7].iter().copied().collect());
+    assert_eq!(total.writes, [9].iter().copied().collect());
+    assert_eq!(
+        total.branches[&2],
+        BranchCount {
+            taken: 2,
+            not_taken: 1
+        }
+    );
+
+    let coverage = Rc::new(RefCell::new(Coverage::default()));
+    let mut ic = Intcode::new(program.clone(), false);
+    ic.add_observer(coverage.clone());
+    ic.queue_input(1);
+    ic.progress_program().unwrap();
+    assert_eq!(
+        coverage.borrow().report(&program),
+        [
+            "*         0: in [9]",
+            "*         2: jt [9], 7    (taken 1, not taken 0)",
+            "          5: out 0",
+            "*         7: hlt",
+            "          8: .data 0",
+            "  rw      9: .data 0",
+            "3 of 4 instructions executed, 1 of 2 branch directions taken"
+        ]
+        .join("\n")
+    );
+
+    // A relative mode condition, as the machine saw it
+    let coverage = Rc::new(RefCell::new(Coverage::default()));
+    let mut ic = Intcode::new(vec![109, 9, 1206, 0, 7, 104, 1, 99, 0, 5], false);
+    ic.add_observer(coverage.clone());
+    ic.progress_program().unwrap();
+    assert_eq!(
+        coverage.borrow().branches[&2],
+        BranchCount {
+            taken: 0,
+            not_taken: 1
+        }
+    );
+}
@@ -0,0 +1,385 @@
+// A debugger for a single machine that one client at a time can drive over TCP. The protocol
+// is line based: each command is one line and gets back one line, either `ok` followed by
+// any results or `error` followed by a message.
+//
+//   step [n]                 run n instructions (default 1)     -> ok ip <ip> | ok input <ip> | ok halted
+//                                                                  | ok looping <start> <period>
+//   continue                 run until something stops it       -> ok breakpoint <ip> | ok input <ip>
+//                                                                  | ok paused <ip> | ok halted
+//                                                                  | ok looping <start> <period>
+//   pause                    stop a continue that's in progress -> ok paused <ip>
+//   break <addr>             set a breakpoint                   -> ok
+//   delete <addr>            clear a breakpoint                 -> ok
+//   breakpoints              list the breakpoints               -> ok <addr>...
+//   read <addr> [count]      read memory (default 1 cell, at most 4096) -> ok <value>...
+//   write <addr> <value>...  write memory from addr onwards     -> ok
+//   regs                     -> ok ip <ip> rb <rb> cycles <n> state new|running|input|halted|looping
+//   input <value>...         queue input                        -> ok
+//   output                   take every output produced so far  -> ok <value>...
+//   backtrace                the inferred call stack, innermost first -> ok #0 <ip> in <function>, ...
+//   quit                     end the session                    -> ok
+//
+// `continue` stops on a breakpoint before executing the instruction there, or when the
+// machine asks for input that hasn't been queued. While it's running the only command that
+// gets looked at is `pause`; anything else waits until the machine stops. When the machine
+// faults during `step` or `continue` the error ends with a backtrace.
+
+use super::{Intcode, IntcodeState};
+use std::collections::BTreeSet;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+// How many instructions to run between checks for a pause
+const PAUSE_INTERVAL: u64 = 10_000;
+// The most cells a single read returns
+const MAX_READ: usize = 4096;
+
+pub struct DebugServer {
+    ic: Intcode,
+    breakpoints: BTreeSet<usize>,
+}
+
+struct Client {
+    stream: TcpStream,
+    pending: Vec<u8>,
+}
+
+impl Client {
+    fn next_line(&mut self) -> Result<Option<String>, String> {
+        loop {
+            if let Some(end) = self.pending.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = self.pending.drain(..=end).collect();
+                return Ok(Some(String::from_utf8_lossy(&line).trim().to_string()));
+            }
+            let mut buf = [0; 512];
+            match self.stream.read(&mut buf) {
+                Ok(0) => return Ok(None),
+                Ok(n) => self.pending.extend_from_slice(&buf[..n]),
+                Err(e) => return Err(format!("Could not read from client: {}", e)),
+            }
+        }
+    }
+
+    fn send(&mut self, reply: &str) -> Result<(), String> {
+        self.stream
+            .write_all(format!("{}\n", reply).as_bytes())
+            .map_err(|e| format!("Could not write to client: {}", e))
+    }
+
+    // Reads whatever has arrived without blocking, and consumes the first pause command in it
+    fn pause_requested(&mut self) -> bool {
+        if self.stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let mut buf = [0; 512];
+        while let Ok(n) = self.stream.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            self.pending.extend_from_slice(&buf[..n]);
+        }
+        if self.stream.set_nonblocking(false).is_err() {
+            return false;
+        }
+
+        let mut paused = false;
+        let mut rest = Vec::new();
+        for line in self.pending.split_inclusive(|b| *b == b'\n') {
+            let complete = line.ends_with(b"\n");
+            if !paused && complete && String::from_utf8_lossy(line).trim() == "pause" {
+                paused = true;
+            } else {
+                rest.extend_from_slice(line);
+            }
+        }
+        self.pending = rest;
+        paused
+    }
+}
+
+fn parse_values<T: std::str::FromStr>(args: &[&str]) -> Result<Vec<T>, String> {
+    args.iter()
+        .map(|a| a.parse().map_err(|_| format!("Invalid number {}", a)))
+        .collect()
+}
+
+fn join(values: &[i64]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+impl DebugServer {
+    pub fn new(mut ic: Intcode) -> Self {
+        if ic.call_stack().is_none() {
+            ic.enable_call_stack();
+        }
+        DebugServer {
+            ic,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> Intcode {
+        self.ic
+    }
+
+    // Serves one client until it quits or disconnects
+    pub fn serve(&mut self, listener: &TcpListener) -> Result<(), String> {
+        let (stream, _) = listener
+            .accept()
+            .map_err(|e| format!("Could not accept a client: {}", e))?;
+        // Replies are tiny so don't hold them back waiting for more to send
+        let _ = stream.set_nodelay(true);
+        let mut client = Client {
+            stream,
+            pending: Vec::new(),
+        };
+        while let Some(line) = client.next_line()? {
+            let reply = if line == "continue" {
+                self.resume(|| client.pause_requested())
+            } else {
+                self.execute(&line)
+            };
+            client.send(&match reply {
+                Ok(result) if result.is_empty() => "ok".to_string(),
+                Ok(result) => format!("ok {}", result),
+                Err(e) => format!("error {}", e),
+            })?;
+            if line == "quit" {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn stopped(&self, state: IntcodeState) -> String {
+        match state {
+            IntcodeState::Done => "halted".to_string(),
+            IntcodeState::PollingInput => format!("input {}", self.ic.ip()),
+            IntcodeState::Looping { start, period } => format!("looping {} {}", start, period),
+            _ => format!("ip {}", self.ic.ip()),
+        }
+    }
+
+    fn step(&mut self) -> Result<IntcodeState, String> {
+        self.ic.step().map_err(|e| {
+            let backtrace = self.ic.backtrace().unwrap_or_default();
+            format!("{} (backtrace: {})", e, backtrace.join(", "))
+        })
+    }
+
+    fn resume(&mut self, mut pause_requested: impl FnMut() -> bool) -> Result<String, String> {
+        let mut count: u64 = 0;
+        loop {
+            let state = self.step()?;
+            if state != IntcodeState::Running {
+                return Ok(self.stopped(state));
+            }
+            if self.breakpoints.contains(&self.ic.ip()) {
+                return Ok(format!("breakpoint {}", self.ic.ip()));
+            }
+            count += 1;
+            if count == PAUSE_INTERVAL {
+                count = 0;
+                if pause_requested() {
+                    return Ok(format!("paused {}", self.ic.ip()));
+                }
+            }
+        }
+    }
+
+    fn execute(&mut self, line: &str) -> Result<String, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => return Err("Empty command".to_string()),
+        };
+        let addr = || -> Result<usize, String> {
+            let addr = parse_values(args.get(..1).ok_or("Missing address")?)?;
+            Ok(addr[0])
+        };
+
+        match command {
+            "step" => {
+                let count = match args.first() {
+                    Some(_) => parse_values(&args[..1])?[0],
+                    None => 1,
+                };
+                let mut state = self.ic.get_state();
+                for _ in 0..count {
+                    state = self.step()?;
+                    if state != IntcodeState::Running {
+                        break;
+                    }
+                }
+                Ok(self.stopped(state))
+            }
+            "continue" => self.resume(|| false),
+            "pause" => Ok(format!("paused {}", self.ic.ip())),
+            "break" => {
+                self.breakpoints.insert(addr()?);
+                Ok(String::new())
+            }
+            "delete" => {
+                let addr = addr()?;
+                if !self.breakpoints.remove(&addr) {
+                    return Err(format!("No breakpoint at {}", addr));
+                }
+                Ok(String::new())
+            }
+            "breakpoints" => Ok(self
+                .breakpoints
+                .iter()
+                .map(|b| b.to_string())
+                .collect::<Vec<String>>()
+                .join(" ")),
+            "read" => {
+                let addr = addr()?;
+                let count = match args.get(1) {
+                    Some(_) => parse_values(&args[1..2])?[0],
+                    None => 1,
+                };
+                if count > MAX_READ {
+                    return Err(format!("Can read at most {} cells", MAX_READ));
+                }
+                let end = addr.checked_add(count).ok_or("Address overflows")?;
+                Ok(join(&self.ic.read_range(addr..end)?))
+            }
+            "write" if args.len() < 2 => Err("Missing value".to_string()),
+            "write" => {
+                let values: Vec<i64> = parse_values(&args[1..])?;
+                self.ic.write_range(addr()?, &values)?;
+                Ok(String::new())
+            }
+            "regs" => {
+                let state = match self.ic.get_state() {
+                    IntcodeState::NotStarted => "new",
+                    IntcodeState::Running => "running",
+                    IntcodeState::PollingInput => "input",
+                    IntcodeState::Done => "halted",
+                    IntcodeState::Looping { .. } => "looping",
+                };
+                Ok(format!(
+                    "ip {} rb {} cycles {} state {}",
+                    self.ic.ip(),
+                    self.ic.rb(),
+                    self.ic.cycles(),
+                    state
+                ))
+            }
+            "input" => {
+                for value in parse_values::<i64>(args)? {
+                    self.ic.queue_input(value);
+                }
+                Ok(String::new())
+            }
+            "output" => {
+                let mut outputs = Vec::new();
+                while let Some(o) = self.ic.dequeue_output() {
+                    outputs.push(o);
+                }
+                Ok(join(&outputs))
+            }
+            "backtrace" => Ok(self.ic.backtrace().unwrap_or_default().join(", ")),
+            "quit" => Ok(String::new()),
+            _ => Err(format!("Unknown command {}", command)),
+        }
+    }
+}
+
+// Runs the server on this thread, since machines can't be sent between threads, and drives
+// it from a client thread that returns every reply it got
+#[cfg(test)]
+fn debug_session(program: Vec<i64>, script: &'static [&'static str]) -> (Vec<String>, Intcode) {
+    use std::io::{BufRead, BufReader};
+    use std::thread;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let client = thread::spawn(move || {
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+        let mut replies = Vec::new();
+        for command in script {
+            writer
+                .write_all(format!("{}\n", command).as_bytes())
+                .unwrap();
+            let mut reply = String::new();
+            reader.read_line(&mut reply).unwrap();
+            replies.push(reply.trim_end().to_string());
+        }
+        replies
+    });
+
+    let mut server = DebugServer::new(Intcode::new(program, false));
+    server.serve(&listener).unwrap();
+    (client.join().unwrap(), server.into_inner())
+}
+
+#[test]
+fn can_debug_a_machine_over_tcp() {
+    // in [11]; mul [11], 2, [11]; out [11]; jt 1, 0
+    let program = vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+    let (replies, ic) = debug_session(
+        program,
+        &[
+            "break 6",
+            "continue",
+            "input 21",
+            "continue",
+            "read 11",
+            "write 11 50",
+            "step",
+            "output",
+            "regs",
+            "breakpoints",
+            "delete 7",
+            "continue",
+            "bogus",
+            "read 0 5000",
+            "read 18446744073709551615 2",
+            "backtrace",
+            "quit",
+        ],
+    );
+    assert_eq!(
+        replies,
+        vec![
+            "ok",
+            "ok input 0",
+            "ok",
+            "ok breakpoint 6",
+            "ok 42",
+            "ok",
+            "ok ip 8",
+            "ok 50",
+            "ok ip 8 rb 0 cycles 3 state running",
+            "ok 6",
+            "error No breakpoint at 7",
+            "ok input 0",
+            "error Unknown command bogus",
+            "error Can read at most 4096 cells",
+            "error Address overflows",
+            "ok #0 0 in main",
+            "ok"
+        ]
+    );
+    assert_eq!(ic.read(11), Ok(50));
+}
+
+#[test]
+fn can_pause_a_running_machine() {
+    // jt 1, 0 forever, paused the second time the client is checked
+    let mut server = DebugServer::new(Intcode::new(vec![1105, 1, 0], false));
+    let mut checks = 0;
+    let reply = server.resume(|| {
+        checks += 1;
+        checks == 2
+    });
+    assert_eq!(reply, Ok("paused 0".to_string()));
+    assert_eq!(server.ic.cycles(), 2 * PAUSE_INTERVAL);
+}
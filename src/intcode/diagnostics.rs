@@ -0,0 +1,162 @@
+use super::{Instruction, Intcode, Trace};
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+// Diagnostic programs check the machine they run on, and say how it went through their
+// outputs. Which outputs mean what depends on the program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Convention {
+    // Day 5's TEST: one output per check, zero when it passed and otherwise how far off it
+    // was, and then the diagnostic code
+    Test,
+    // Day 9's BOOST: just the keycode when everything works, and otherwise every output is
+    // the opcode of an instruction that malfunctioned
+    Boost,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Failure {
+    // Which output the failure was, counting from 0
+    pub index: usize,
+    // How far off the check was (Test), or the malfunctioning opcode (Boost)
+    pub value: i64,
+    // The instruction that produced it, from the trace
+    pub ip: usize,
+    pub instruction: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub convention: Convention,
+    // The outputs that were checks, every output but the code for Test and every failure
+    // for Boost
+    pub checks: usize,
+    pub failures: Vec<Failure>,
+    pub code: Option<i64>,
+}
+
+impl Report {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty() && self.code.is_some()
+    }
+}
+
+// Runs a diagnostic program on a single input, keeping track of where each output came from
+pub fn diagnose(program: Vec<i64>, input: i64, convention: Convention) -> Result<Report, String> {
+    let mut ic = Intcode::new(program, true);
+    let trace = Rc::new(RefCell::new(Trace::new(1)));
+    ic.add_observer(trace.clone());
+
+    let mut outputs = Vec::new();
+    for output in ic.into_outputs(vec![input]) {
+        let output = output?;
+        // Outputs are handed back as soon as they're produced, so the last instruction traced
+        // is the one that produced this one
+        let trace = trace.borrow();
+        let entry = trace
+            .entries()
+            .last()
+            .ok_or("Output without an instruction")?;
+        let instruction = Instruction::decode(&entry.words, 0)
+            .map(|instruction| instruction.to_string())
+            .unwrap_or_else(|_| format!("{:?}", entry.words));
+        outputs.push((output, entry.ip, instruction));
+    }
+
+    let code = match (convention, outputs.len()) {
+        (Convention::Test, _) | (Convention::Boost, 1) => outputs.pop().map(|(code, _, _)| code),
+        (Convention::Boost, _) => None,
+    };
+    let failures: Vec<Failure> = outputs
+        .iter()
+        .enumerate()
+        .filter(|(_, (value, _, _))| convention == Convention::Boost || *value != 0)
+        .map(|(index, (value, ip, instruction))| Failure {
+            index,
+            value: *value,
+            ip: *ip,
+            instruction: instruction.clone(),
+        })
+        .collect();
+    Ok(Report {
+        convention,
+        checks: outputs.len(),
+        failures,
+        code,
+    })
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.convention {
+            Convention::Test => {
+                writeln!(f, "{} checks, {} failed", self.checks, self.failures.len())?;
+                for failure in &self.failures {
+                    writeln!(
+                        f,
+                        "    check {} failed with {} ({} at {})",
+                        failure.index, failure.value, failure.instruction, failure.ip
+                    )?;
+                }
+            }
+            Convention::Boost => {
+                writeln!(f, "{} malfunctioning opcodes", self.failures.len())?;
+                for failure in &self.failures {
+                    writeln!(
+                        f,
+                        "    opcode {} ({} at {})",
+                        failure.value, failure.instruction, failure.ip
+                    )?;
+                }
+            }
+        }
+        match self.code {
+            Some(code) => writeln!(f, "diagnostic code {}", code),
+            None => writeln!(f, "no diagnostic code"),
+        }
+    }
+}
+
+#[test]
+fn reports_failing_checks_and_where_they_came_from() {
+    let program = super::loader::load_program("res/day_05.txt").unwrap();
+    let report = diagnose(program, 1, Convention::Test).unwrap();
+    assert!(report.passed());
+    assert_eq!(report.checks, 9);
+
+    // out 0; out [9]; out 0; out 1234; hlt; .data 5
+    let program = vec![104, 0, 4, 9, 104, 0, 104, 1234, 99, 5];
+    let report = diagnose(program, 1, Convention::Test).unwrap();
+    assert_eq!(
+        report,
+        Report {
+            convention: Convention::Test,
+            checks: 3,
+            failures: vec![Failure {
+                index: 1,
+                value: 5,
+                ip: 2,
+                instruction: "out [9]".to_string()
+            }],
+            code: Some(1234)
+        }
+    );
+    assert_eq!(
+        report.to_string(),
+        "3 checks, 1 failed\n    check 1 failed with 5 (out [9] at 2)\ndiagnostic code 1234\n"
+    );
+
+    let program = super::loader::load_program("res/day_09.txt").unwrap();
+    assert!(diagnose(program, 1, Convention::Boost).unwrap().passed());
+
+    // Anything but a single output is a list of malfunctioning opcodes
+    // out 203; out [7]; hlt; .data 9
+    let program = vec![104, 203, 4, 7, 99, 0, 0, 9];
+    let report = diagnose(program, 1, Convention::Boost).unwrap();
+    assert!(!report.passed());
+    assert_eq!(
+        report.to_string(),
+        "2 malfunctioning opcodes\n    opcode 203 (out 203 at 0)\n    opcode 9 (out [7] at 2)\nno diagnostic code\n"
+    );
+}
@@ -0,0 +1,62 @@
+use super::instruction::Instruction;
+
+// Instructions are written the way they display (see instruction.rs), and anything that
+// doesn't decode as an instruction is shown as a .data cell.
+
+#[derive(Debug, PartialEq)]
+pub struct Line {
+    pub addr: usize,
+    pub len: usize,
+    pub text: String,
+}
+
+// Decodes the instruction at addr, or None if the cell isn't a valid instruction
+pub fn disassemble_at(memory: &[i64], addr: usize) -> Option<Line> {
+    let instruction = Instruction::decode(memory, addr).ok()?;
+    Some(Line {
+        addr,
+        len: instruction.size(),
+        text: instruction.to_string(),
+    })
+}
+
+pub fn data(memory: &[i64], addr: usize) -> Line {
+    Line {
+        addr,
+        len: 1,
+        text: format!(".data {}", memory[addr]),
+    }
+}
+
+// A straight linear sweep, which can be thrown off by data that happens to decode
+pub fn disassemble(memory: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut addr = 0;
+    while addr < memory.len() {
+        let line = disassemble_at(memory, addr).unwrap_or_else(|| data(memory, addr));
+        addr += line.len;
+        lines.push(line);
+    }
+    lines
+}
+
+#[test]
+fn can_disassemble_instructions() {
+    let memory = vec![1002, 4, 3, 4, 21101, -1, 7, 2, 109, -5, 99, 123, 5];
+    let text: Vec<String> = disassemble(&memory)
+        .iter()
+        .map(|l| format!("{}: {}", l.addr, l.text))
+        .collect();
+    assert_eq!(
+        text,
+        vec![
+            "0: mul [4], 3, [4]",
+            "4: add -1, 7, [rb+2]",
+            "8: arb -5",
+            "10: hlt",
+            "11: .data 123",
+            "12: .data 5"
+        ]
+    );
+    assert_eq!(disassemble_at(&memory, 11), None);
+}
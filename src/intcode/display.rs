@@ -0,0 +1,199 @@
+use super::disassembler::{data, disassemble_at, Line};
+use super::Intcode;
+use colored::*;
+use std::fmt;
+
+// How much of the program to show either side of ip, in instructions
+const CODE_BEFORE: usize = 3;
+const CODE_AFTER: usize = 4;
+// How much memory to show either side of rb, in cells
+const STACK_CELLS: isize = 8;
+
+fn decode(memory: &[i64], addr: usize) -> Line {
+    disassemble_at(memory, addr).unwrap_or_else(|| data(memory, addr))
+}
+
+// Styles text, unless the machine is being formatted plainly (with {:#})
+fn paint(plain: bool, text: &str, style: fn(ColoredString) -> ColoredString) -> String {
+    if plain {
+        text.to_string()
+    } else {
+        style(text.normal()).to_string()
+    }
+}
+
+fn join(values: impl Iterator<Item = i64>) -> String {
+    let values: Vec<String> = values.map(|v| v.to_string()).collect();
+    if values.is_empty() {
+        "(none)".to_string()
+    } else {
+        values.join(", ")
+    }
+}
+
+impl Intcode {
+    // Instructions can't be decoded backwards, so this sweeps forward from the earliest
+    // address shortly before ip that lines up with it
+    fn code_window(&self) -> Vec<Line> {
+        let memory = self.memory.to_vec();
+        let lookback = CODE_BEFORE * 4;
+        let mut before = Vec::new();
+        for start in self.ip.saturating_sub(lookback)..=self.ip {
+            let mut lines = Vec::new();
+            let mut addr = start;
+            while addr < self.ip {
+                let line = decode(&memory, addr);
+                addr += line.len;
+                lines.push(line);
+            }
+            if addr == self.ip {
+                before = lines;
+                break;
+            }
+        }
+
+        let mut window: Vec<Line> = before.into_iter().rev().take(CODE_BEFORE).collect();
+        window.reverse();
+        let mut addr = self.ip;
+        while addr < self.memory.len() && window.len() < CODE_BEFORE + CODE_AFTER + 1 {
+            let line = decode(&memory, addr);
+            addr += line.len;
+            window.push(line);
+        }
+        window
+    }
+
+    fn fmt_stack(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain = f.alternate();
+        let start = self.rb.saturating_sub(STACK_CELLS).max(0) as usize;
+        let end = (self.rb.saturating_add(STACK_CELLS).max(0) as usize).min(self.memory.len());
+        if self.rb < 0 || start >= end {
+            return writeln!(f, "{}", paint(plain, "rb is outside memory", |s| s.red()));
+        }
+        let width = self
+            .memory
+            .range(start..end)
+            .iter()
+            .map(|n| n.to_string().len())
+            .max()
+            .unwrap_or(0);
+        for row in (start..end).step_by(STACK_CELLS as usize) {
+            let cells: Vec<String> = (row..(row + STACK_CELLS as usize).min(end))
+                .map(|addr| {
+                    let cell = format!("{:>w$}", self.memory[addr], w = width);
+                    if addr as isize == self.rb {
+                        paint(plain, &cell, |s| s.cyan().bold())
+                    } else {
+                        cell
+                    }
+                })
+                .collect();
+            writeln!(f, "{:>6} | {}", row, cells.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+// Everything about where the machine is up to, for printing when something goes wrong.
+// Formatting with {:#} leaves out the colours.
+impl fmt::Display for Intcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plain = f.alternate();
+        let bold = |text| paint(plain, text, |s| s.bold());
+        writeln!(
+            f,
+            "{} {}  {} {}  {} {:?}  {} {}",
+            bold("ip"),
+            self.ip,
+            bold("rb"),
+            self.rb,
+            bold("state"),
+            self.state,
+            bold("cycles"),
+            self.cycles
+        )?;
+
+        writeln!(f, "{}", bold("code"))?;
+        if self.ip >= self.memory.len() {
+            writeln!(f, "{}", paint(plain, "ip is outside memory", |s| s.red()))?;
+        } else {
+            for line in self.code_window() {
+                let text = format!("{:>6}: {}", line.addr, line.text);
+                if line.addr == self.ip {
+                    let highlight = |text| paint(plain, text, |s| s.yellow().bold());
+                    writeln!(f, "{} {}", highlight(">"), highlight(&text))?;
+                } else {
+                    writeln!(f, "  {}", text)?;
+                }
+            }
+        }
+
+        writeln!(f, "{}", bold("stack"))?;
+        self.fmt_stack(f)?;
+
+        writeln!(
+            f,
+            "{} {}",
+            bold("input"),
+            join(self.input_queue.iter().copied())
+        )?;
+        write!(
+            f,
+            "{} {}",
+            bold("output"),
+            join(self.output_queue.iter().copied())
+        )?;
+        if let Some(backtrace) = self.backtrace() {
+            write!(f, "\n{} {}", bold("backtrace"), backtrace.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn can_display_the_machine_state() {
+    // add 1, 2, [rb+0]; arb 2; out [rb-2]; in [rb+0]; hlt   (with the stack from 12)
+    let mut program = vec![21101, 1, 2, 0, 109, 2, 204, -2, 203, 0, 99];
+    program.resize(19, 0);
+    let mut ic = Intcode::new(program, false);
+    ic.set_rb(12);
+    ic.progress_program().unwrap();
+    assert_eq!(
+        format!("{:#}", ic),
+        [
+            "ip 8  rb 14  state PollingInput  cycles 3",
+            "code",
+            "       0: add 1, 2, [rb+0]",
+            "       4: arb 2",
+            "       6: out [rb-2]",
+            ">      8: in [rb+0]",
+            "      10: hlt",
+            "      11: .data 0",
+            "      12: in [0]",
+            "      14: .data 0",
+            "stack",
+            "     6 | 204  -2 203   0  99   0   3   0",
+            "    14 |   0   0   0   0   0",
+            "input (none)",
+            "output 3"
+        ]
+        .join("\n")
+    );
+
+    let mut ic = Intcode::new(vec![104, 5, 98], false);
+    ic.enable_call_stack();
+    assert!(ic.progress_program().is_err());
+    let text = format!("{:#}", ic);
+    assert!(text.contains(">      2: .data 98\n"));
+    assert!(text.ends_with("output 5\nbacktrace #0 2 in main"));
+
+    // rb right at the top of the address space
+    let mut ic = Intcode::new(vec![109, i64::MAX, 98], false);
+    assert!(ic.progress_program().is_err());
+    assert!(format!("{:#}", ic).contains("stack\nrb is outside memory\n"));
+
+    // ip past the end of memory, with nothing to show for the code
+    let mut ic = Intcode::new(vec![1105, 1, 100], false);
+    assert!(ic.progress_program().is_err());
+    assert!(format!("{:#}", ic).contains("code\nip is outside memory\nstack\n"));
+}
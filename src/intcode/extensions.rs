@@ -0,0 +1,146 @@
+use super::Intcode;
+
+// An extension is invoked with ip on its opcode and the parameter modes already parsed.
+// It reads and writes its parameters through the machine, and must leave ip on the
+// next instruction (advance_ip) or wherever it jumps to (jump_to) before returning.
+pub trait Extension {
+    fn execute(&mut self, ic: &mut Intcode, modes: &[u8]) -> Result<(), String>;
+}
+
+// Reads one parameter and records it, handy for printf style debugging of programs.
+#[derive(Default)]
+pub struct DebugPrint {
+    pub printed: Vec<i64>,
+    pub echo: bool,
+}
+
+impl Extension for DebugPrint {
+    fn execute(&mut self, ic: &mut Intcode, modes: &[u8]) -> Result<(), String> {
+        let val = ic.read_next_ins_param(modes[0])?;
+        if self.echo {
+            eprintln!("[intcode debug @ {}] {}", ic.ip() - 1, val);
+        }
+        self.printed.push(val);
+        ic.advance_ip();
+        Ok(())
+    }
+}
+
+// Reads two parameters and fails the machine if they differ.
+pub struct Assert;
+
+impl Extension for Assert {
+    fn execute(&mut self, ic: &mut Intcode, modes: &[u8]) -> Result<(), String> {
+        let start = ic.ip();
+        let actual = ic.read_next_ins_param(modes[0])?;
+        let expected = ic.read_next_ins_param(modes[1])?;
+        if actual != expected {
+            return Err(format!(
+                "Assertion failed at {}: {} != {}",
+                start, actual, expected
+            ));
+        }
+        ic.advance_ip();
+        Ok(())
+    }
+}
+
+type TrapHandler = Box<dyn FnMut(&mut Intcode) -> Result<(), String>>;
+
+// A syscall like trap, the first parameter selects a handler which is then free to do
+// its own I/O on the machine. Handlers run after ip has moved past the trap instruction.
+#[derive(Default)]
+pub struct Trap {
+    handlers: std::collections::HashMap<i64, TrapHandler>,
+}
+
+impl Trap {
+    pub fn on<F>(&mut self, number: i64, handler: F)
+    where
+        F: FnMut(&mut Intcode) -> Result<(), String> + 'static,
+    {
+        self.handlers.insert(number, Box::new(handler));
+    }
+}
+
+impl Extension for Trap {
+    fn execute(&mut self, ic: &mut Intcode, modes: &[u8]) -> Result<(), String> {
+        let start = ic.ip();
+        let number = ic.read_next_ins_param(modes[0])?;
+        ic.advance_ip();
+        match self.handlers.get_mut(&number) {
+            Some(handler) => handler(ic),
+            None => Err(format!("Unhandled trap {} at {}", number, start)),
+        }
+    }
+}
+
+#[cfg(test)]
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn cannot_override_builtin_opcodes() {
+    let mut ic = Intcode::new(vec![99], false);
+    assert!(ic
+        .register_extension(1, Rc::new(RefCell::new(Assert)))
+        .is_err());
+    assert!(ic
+        .register_extension(99, Rc::new(RefCell::new(Assert)))
+        .is_err());
+    assert!(ic
+        .register_extension(42, Rc::new(RefCell::new(Assert)))
+        .is_ok());
+}
+
+#[test]
+fn can_run_debug_print_and_assert_extensions() {
+    let debug = Rc::new(RefCell::new(DebugPrint::default()));
+    let memory = vec![1101, 2, 3, 10, 50, 10, 1051, 10, 5, 99, 0];
+    let mut ic = Intcode::new(memory.clone(), false);
+    ic.register_extension(50, debug.clone()).unwrap();
+    ic.register_extension(51, Rc::new(RefCell::new(Assert)))
+        .unwrap();
+    ic.progress_program().unwrap();
+    assert_eq!(debug.borrow().printed, vec![5]);
+
+    let mut memory = memory;
+    memory[8] = 6;
+    ic = Intcode::new(memory, false);
+    ic.register_extension(50, debug).unwrap();
+    ic.register_extension(51, Rc::new(RefCell::new(Assert)))
+        .unwrap();
+    assert_eq!(
+        ic.progress_program(),
+        Err("Assertion failed at 6: 5 != 6".to_string())
+    );
+
+    // Too big for its modes to be parsed, so it isn't the extension's opcode
+    ic = Intcode::new(vec![4_294_967_350, 99], false);
+    ic.register_extension(50, Rc::new(RefCell::new(DebugPrint::default())))
+        .unwrap();
+    assert_eq!(
+        ic.progress_program(),
+        Err("Unsupported opcode: 4294967350".to_string())
+    );
+}
+
+#[test]
+fn can_dispatch_traps() {
+    let mut trap = Trap::default();
+    trap.on(1, |ic| match ic.take_input() {
+        Some(n) => {
+            ic.push_output(n * 2);
+            Ok(())
+        }
+        None => Err("No input for trap 1".to_string()),
+    });
+    let mut ic = Intcode::new(vec![1177, 1, 1177, 3, 99], false);
+    ic.register_extension(77, Rc::new(RefCell::new(trap)))
+        .unwrap();
+    ic.queue_input(21);
+    assert_eq!(
+        ic.progress_program(),
+        Err("Unhandled trap 3 at 2".to_string())
+    );
+    assert_eq!(ic.dequeue_output(), Some(42));
+}
@@ -0,0 +1,371 @@
+use super::{Intcode, PagedMemory};
+use std::fmt;
+
+// The built-in instructions, decoded. Parameters keep their modes (even where a mode makes
+// no sense, like an immediate mode write), so encoding a decoded instruction always gives
+// back the cells it came from. The interpreter, disassembler and assembler all go through
+// here.
+
+// (opcode, mnemonic, parameter count) for every built-in instruction
+const OPCODES: [(i64, &str, usize); 10] = [
+    (1, "add", 3),
+    (2, "mul", 3),
+    (3, "in", 1),
+    (4, "out", 1),
+    (5, "jt", 2),
+    (6, "jf", 2),
+    (7, "lt", 3),
+    (8, "eq", 3),
+    (9, "arb", 1),
+    (99, "hlt", 0),
+];
+
+pub fn mnemonic(op: i64) -> Option<&'static str> {
+    OPCODES
+        .iter()
+        .find(|(code, _, _)| *code == op)
+        .map(|(_, name, _)| *name)
+}
+
+// Zero for anything that isn't a built-in instruction
+pub fn param_count(op: i64) -> usize {
+    OPCODES
+        .iter()
+        .find(|(code, _, _)| *code == op)
+        .map_or(0, |(_, _, count)| *count)
+}
+
+pub fn opcode(mnemonic: &str) -> Option<i64> {
+    OPCODES
+        .iter()
+        .find(|(_, name, _)| *name == mnemonic)
+        .map(|(code, _, _)| *code)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Position,
+    Immediate,
+    Relative,
+}
+
+impl Mode {
+    pub fn from_digit(digit: i64) -> Option<Mode> {
+        match digit {
+            0 => Some(Mode::Position),
+            1 => Some(Mode::Immediate),
+            2 => Some(Mode::Relative),
+            _ => None,
+        }
+    }
+
+    // How the VM has always treated modes: anything it doesn't know reads as immediate
+    pub(super) fn lenient(digit: u8) -> Mode {
+        Mode::from_digit(i64::from(digit)).unwrap_or(Mode::Immediate)
+    }
+
+    pub fn digit(self) -> i64 {
+        match self {
+            Mode::Position => 0,
+            Mode::Immediate => 1,
+            Mode::Relative => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Param {
+    pub mode: Mode,
+    pub value: i64,
+}
+
+impl Param {
+    pub fn new(mode: Mode, value: i64) -> Self {
+        Param { mode, value }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    Add(Param, Param, Param),
+    Mul(Param, Param, Param),
+    In(Param),
+    Out(Param),
+    JumpIfTrue(Param, Param),
+    JumpIfFalse(Param, Param),
+    LessThan(Param, Param, Param),
+    Equals(Param, Param, Param),
+    AdjustBase(Param),
+    Halt,
+}
+
+// Anything an instruction can be decoded from
+pub trait Cells {
+    fn cell(&self, addr: usize) -> Option<i64>;
+}
+
+impl Cells for [i64] {
+    fn cell(&self, addr: usize) -> Option<i64> {
+        self.get(addr).copied()
+    }
+}
+
+impl Cells for Vec<i64> {
+    fn cell(&self, addr: usize) -> Option<i64> {
+        self.get(addr).copied()
+    }
+}
+
+impl Cells for PagedMemory {
+    fn cell(&self, addr: usize) -> Option<i64> {
+        self.get(addr)
+    }
+}
+
+// Reads through any devices mapped into the machine's memory, like the machine itself does
+impl Cells for Intcode {
+    fn cell(&self, addr: usize) -> Option<i64> {
+        if self.in_bounds(addr) {
+            Some(self.load(addr))
+        } else {
+            None
+        }
+    }
+}
+
+// The modes of a built-in instruction's parameters, first parameter first
+pub fn modes(opcode: i64) -> Result<Vec<Mode>, String> {
+    let count = param_count(opcode % 100);
+    let mut modes = Vec::new();
+    for param in 1..=count {
+        let digit = opcode / 10i64.pow(param as u32 + 1) % 10;
+        let mode = Mode::from_digit(digit).ok_or_else(|| {
+            format!(
+                "Invalid mode {} for parameter {} of {}",
+                digit, param, opcode
+            )
+        })?;
+        modes.push(mode);
+    }
+    if opcode / 10i64.pow(count as u32 + 2) != 0 {
+        return Err(format!("Too many parameter modes in {}", opcode));
+    }
+    Ok(modes)
+}
+
+impl Instruction {
+    // None unless op is a built-in instruction taking that many parameters
+    pub fn new(op: i64, params: &[Param]) -> Option<Instruction> {
+        use Instruction::*;
+        Some(match (op, params) {
+            (1, &[a, b, c]) => Add(a, b, c),
+            (2, &[a, b, c]) => Mul(a, b, c),
+            (3, &[a]) => In(a),
+            (4, &[a]) => Out(a),
+            (5, &[a, b]) => JumpIfTrue(a, b),
+            (6, &[a, b]) => JumpIfFalse(a, b),
+            (7, &[a, b, c]) => LessThan(a, b, c),
+            (8, &[a, b, c]) => Equals(a, b, c),
+            (9, &[a]) => AdjustBase(a),
+            (99, &[]) => Halt,
+            _ => return None,
+        })
+    }
+
+    pub fn decode<M: Cells + ?Sized>(memory: &M, ip: usize) -> Result<Instruction, String> {
+        let opcode = Instruction::opcode_at(memory, ip)?;
+        Instruction::decode_with(memory, ip, opcode % 100, &modes(opcode)?)
+    }
+
+    // Like decode, but with the VM's tolerance for bad modes (see Mode::lenient)
+    pub(super) fn decode_lenient<M: Cells + ?Sized>(
+        memory: &M,
+        ip: usize,
+    ) -> Result<Instruction, String> {
+        let opcode = Instruction::opcode_at(memory, ip)?;
+        let modes: Vec<Mode> = (0..param_count(opcode % 100))
+            .map(|i| Mode::lenient((opcode / 10i64.pow(i as u32 + 2) % 10) as u8))
+            .collect();
+        Instruction::decode_with(memory, ip, opcode % 100, &modes)
+    }
+
+    fn opcode_at<M: Cells + ?Sized>(memory: &M, ip: usize) -> Result<i64, String> {
+        let opcode = memory
+            .cell(ip)
+            .ok_or_else(|| format!("No instruction at {}, past the end of memory", ip))?;
+        match mnemonic(opcode % 100) {
+            Some(_) if opcode >= 0 => Ok(opcode),
+            _ => Err(format!("Unknown opcode {} at {}", opcode, ip)),
+        }
+    }
+
+    fn decode_with<M: Cells + ?Sized>(
+        memory: &M,
+        ip: usize,
+        op: i64,
+        modes: &[Mode],
+    ) -> Result<Instruction, String> {
+        let mut params = Vec::new();
+        for (i, &mode) in modes.iter().enumerate() {
+            let addr = ip + 1 + i;
+            let value = memory
+                .cell(addr)
+                .ok_or_else(|| format!("Instruction runs past the end of memory at {}", addr))?;
+            params.push(Param::new(mode, value));
+        }
+        Ok(Instruction::new(op, &params).unwrap())
+    }
+
+    pub fn op(&self) -> i64 {
+        use Instruction::*;
+        match self {
+            Add(..) => 1,
+            Mul(..) => 2,
+            In(..) => 3,
+            Out(..) => 4,
+            JumpIfTrue(..) => 5,
+            JumpIfFalse(..) => 6,
+            LessThan(..) => 7,
+            Equals(..) => 8,
+            AdjustBase(..) => 9,
+            Halt => 99,
+        }
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        use Instruction::*;
+        match *self {
+            Add(a, b, c) | Mul(a, b, c) | LessThan(a, b, c) | Equals(a, b, c) => vec![a, b, c],
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => vec![a, b],
+            In(a) | Out(a) | AdjustBase(a) => vec![a],
+            Halt => vec![],
+        }
+    }
+
+    // The parameters the instruction reads, which for a jump includes its target
+    pub fn sources(&self) -> Vec<Param> {
+        use Instruction::*;
+        match *self {
+            Add(a, b, _) | Mul(a, b, _) | LessThan(a, b, _) | Equals(a, b, _) => vec![a, b],
+            JumpIfTrue(a, b) | JumpIfFalse(a, b) => vec![a, b],
+            Out(a) | AdjustBase(a) => vec![a],
+            In(_) | Halt => vec![],
+        }
+    }
+
+    // The parameter the instruction writes to
+    pub fn dest(&self) -> Option<Param> {
+        use Instruction::*;
+        match *self {
+            Add(_, _, c) | Mul(_, _, c) | LessThan(_, _, c) | Equals(_, _, c) => Some(c),
+            In(a) => Some(a),
+            _ => None,
+        }
+    }
+
+    // Whether a jump with an immediate condition is always (Some(true)) or never taken
+    pub fn taken(&self) -> Option<bool> {
+        let (cond, when) = match *self {
+            Instruction::JumpIfTrue(cond, _) => (cond, true),
+            Instruction::JumpIfFalse(cond, _) => (cond, false),
+            _ => return None,
+        };
+        match cond.mode {
+            Mode::Immediate => Some((cond.value != 0) == when),
+            _ => None,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        mnemonic(self.op()).unwrap()
+    }
+
+    // How many cells the instruction takes up
+    pub fn size(&self) -> usize {
+        param_count(self.op()) + 1
+    }
+
+    pub fn encode(&self) -> Vec<i64> {
+        let params = self.params();
+        let opcode = params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| param.mode.digit() * 10i64.pow(i as u32 + 2))
+            .sum::<i64>()
+            + self.op();
+        std::iter::once(opcode)
+            .chain(params.iter().map(|param| param.value))
+            .collect()
+    }
+}
+
+// Operands are written as 12 for immediate, [12] for position and [rb+12] for relative mode
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            Mode::Position => write!(f, "[{}]", self.value),
+            Mode::Relative if self.value < 0 => write!(f, "[rb{}]", self.value),
+            Mode::Relative => write!(f, "[rb+{}]", self.value),
+            Mode::Immediate => write!(f, "{}", self.value),
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let params: Vec<String> = self.params().iter().map(|p| p.to_string()).collect();
+        if params.is_empty() {
+            write!(f, "{}", self.mnemonic())
+        } else {
+            write!(f, "{} {}", self.mnemonic(), params.join(", "))
+        }
+    }
+}
+
+#[test]
+fn instructions_round_trip_through_memory() {
+    for &(op, _, count) in &OPCODES {
+        for modes in 0..3i64.pow(count as u32) {
+            let mut opcode = op;
+            for i in 0..count {
+                opcode += modes / 3i64.pow(i as u32) % 3 * 10i64.pow(i as u32 + 2);
+            }
+            let cells: Vec<i64> = std::iter::once(opcode)
+                .chain((0..count as i64).map(|i| 7 - i * 5))
+                .collect();
+            let instruction = Instruction::decode(&cells, 0).unwrap();
+            assert_eq!(instruction.encode(), cells);
+            assert_eq!(instruction.size(), cells.len());
+            assert_eq!(instruction.op(), op);
+        }
+    }
+
+    let memory = vec![1002, 4, 3, 4, 21101, -1, 7, 2];
+    let add = Instruction::decode(&memory, 4).unwrap();
+    assert_eq!(
+        add,
+        Instruction::Add(
+            Param::new(Mode::Immediate, -1),
+            Param::new(Mode::Immediate, 7),
+            Param::new(Mode::Relative, 2)
+        )
+    );
+    assert_eq!(add.to_string(), "add -1, 7, [rb+2]");
+    assert_eq!(
+        Instruction::decode(&memory, 1),
+        Ok(Instruction::Out(Param::new(Mode::Position, 3)))
+    );
+
+    assert_eq!(
+        Instruction::decode(&vec![42], 0),
+        Err("Unknown opcode 42 at 0".to_string())
+    );
+    assert_eq!(
+        Instruction::decode(&vec![1302, 0, 0, 0], 0),
+        Err("Invalid mode 3 for parameter 1 of 1302".to_string())
+    );
+    assert_eq!(
+        Instruction::decode(&memory[..6], 4),
+        Err("Instruction runs past the end of memory at 6".to_string())
+    );
+}
@@ -0,0 +1,134 @@
+use std::collections::{BTreeMap, HashMap};
+
+// A relocatable module, as produced by the assembler. The code is laid out as if the module
+// started at address 0, and every cell holding an address is listed so the linker can patch
+// it once it knows where the module ends up: relocations get the module's base added, and
+// imports get the address of the symbol they refer to added. Either way the cell already
+// holds any offset from the address (the 2 in `[table+2]`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Object {
+    pub name: String,
+    pub code: Vec<i64>,
+    // Symbols other modules can refer to, as offsets into code
+    pub exports: BTreeMap<String, usize>,
+    pub relocations: Vec<usize>,
+    pub imports: Vec<Import>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Import {
+    pub cell: usize,
+    pub symbol: String,
+}
+
+// Lays the modules out one after another in the order given, so the first one holds the
+// entry point, and patches every address. The result is ready for Intcode::new.
+pub fn link(objects: &[Object]) -> Result<Vec<i64>, String> {
+    let mut bases = Vec::new();
+    let mut base = 0;
+    for object in objects {
+        bases.push(base);
+        base += object.code.len();
+    }
+
+    let mut symbols: HashMap<&str, (usize, &str)> = HashMap::new();
+    for (object, base) in objects.iter().zip(&bases) {
+        for (symbol, offset) in &object.exports {
+            if let Some((_, other)) = symbols.insert(symbol, (base + offset, &object.name)) {
+                return Err(format!(
+                    "Symbol {} is exported by both {} and {}",
+                    symbol, other, object.name
+                ));
+            }
+        }
+    }
+
+    let mut program = Vec::with_capacity(base);
+    for (object, base) in objects.iter().zip(&bases) {
+        let mut code = object.code.clone();
+        // Objects can be built by hand, so nothing about them is taken on trust
+        let mut patch = |cell: usize, addr: usize, what: &str| {
+            let value = code
+                .get_mut(cell)
+                .ok_or_else(|| format!("{} {} out of range in {}", what, cell, object.name))?;
+            *value = value
+                .checked_add(addr as i64)
+                .ok_or_else(|| format!("{} {} overflows in {}", what, cell, object.name))?;
+            Ok::<(), String>(())
+        };
+        for &cell in &object.relocations {
+            patch(cell, *base, "Relocation")?;
+        }
+        for import in &object.imports {
+            let (addr, _) = symbols
+                .get(import.symbol.as_str())
+                .ok_or_else(|| format!("Undefined symbol {} in {}", import.symbol, object.name))?;
+            patch(import.cell, *addr, "Import")?;
+        }
+        program.extend(code);
+    }
+    Ok(program)
+}
+
+#[test]
+fn can_link_modules_that_call_each_other() {
+    use super::assembler::assemble;
+    use super::Intcode;
+
+    let main = assemble(
+        "main",
+        "
+        .import double
+        .export result
+                arb stack
+                in [rb+1]
+                add back, 0, [rb+0]     # save the return address
+                jt 1, double
+        back:   out [result]
+                hlt
+        result: .data 0
+        stack:  .data 0, 0
+        ",
+    )
+    .unwrap();
+    let lib = assemble(
+        "lib",
+        "
+        .import result
+        .export double
+        double: mul [rb+1], 2, [result]
+                jt 1, [rb+0]
+        ",
+    )
+    .unwrap();
+    assert_eq!(main.relocations, vec![1, 5, 12]);
+    assert_eq!(main.imports.len(), 1);
+
+    let program = link(&[main.clone(), lib.clone()]).unwrap();
+    let outputs: Vec<i64> = Intcode::run_iter(program, vec![21])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(outputs, vec![42]);
+
+    assert_eq!(
+        link(std::slice::from_ref(&main)),
+        Err("Undefined symbol double in main".to_string())
+    );
+    assert_eq!(
+        link(&[main.clone(), lib.clone(), lib.clone()]),
+        Err("Symbol double is exported by both lib and lib".to_string())
+    );
+
+    let mut broken = main.clone();
+    broken.relocations.push(100);
+    assert_eq!(
+        link(&[broken, lib.clone()]),
+        Err("Relocation 100 out of range in main".to_string())
+    );
+    let mut broken = main;
+    broken.code[1] = i64::MAX;
+    assert_eq!(
+        link(&[lib, broken]),
+        Err("Relocation 1 overflows in main".to_string())
+    );
+}
@@ -0,0 +1,355 @@
+use super::instruction::{modes, Mode};
+use super::validator::{check_modes, reachable};
+use super::{Instruction, Param};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+// Static checks over the instructions reachable from address 0 (see validator::reachable),
+// so jumps through memory or the stack aren't followed, and code only reached that way is
+// reported as unreachable, unless the program rewrites its own code, in which case nothing
+// is. Every warning has a kind, for filtering or tooling, and a message for people.
+
+// The instruction sets of each day's machine
+pub const DAY_02: &[i64] = &[1, 2, 99];
+pub const DAY_05: &[i64] = &[1, 2, 3, 4, 5, 6, 7, 8, 99];
+pub const DAY_09: &[i64] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+
+#[derive(Debug, PartialEq)]
+pub struct Warning {
+    pub addr: usize,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+// A reachable instruction and where it is
+struct Reached {
+    addr: usize,
+    instruction: Instruction,
+}
+
+impl Reached {
+    fn cells(&self) -> std::ops::Range<usize> {
+        self.addr..self.addr + self.instruction.size()
+    }
+
+    // Where execution can go next, as far as can be told without running it
+    fn successors(&self) -> Vec<i64> {
+        let next = self.cells().end as i64;
+        match self.instruction {
+            Instruction::JumpIfTrue(_, target) | Instruction::JumpIfFalse(_, target) => {
+                let mut successors = Vec::new();
+                if target.mode == Mode::Immediate {
+                    successors.push(target.value);
+                }
+                if self.instruction.taken() != Some(true) {
+                    successors.push(next);
+                }
+                successors
+            }
+            Instruction::Halt => vec![],
+            _ => vec![next],
+        }
+    }
+
+    // Position mode addresses read, and the one written (if any)
+    fn accesses(&self) -> (Vec<i64>, Option<i64>) {
+        let position = |param: &Param| param.mode == Mode::Position;
+        let reads = self.instruction.sources();
+        let reads = reads.iter().filter(|p| position(p)).map(|p| p.value);
+        let write = self.instruction.dest().filter(position).map(|p| p.value);
+        (reads.collect(), write)
+    }
+
+    fn is_jump(&self) -> bool {
+        matches!(
+            self.instruction,
+            Instruction::JumpIfTrue(..) | Instruction::JumpIfFalse(..)
+        )
+    }
+}
+
+// Runs of cells that aren't part of a reachable instruction or used as data, but still
+// decode as a sequence of instructions ending in a halt or a jump
+fn unreachable_code(
+    program: &[i64],
+    covered: &BTreeMap<usize, usize>,
+    data: &BTreeSet<i64>,
+) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut addr = 0;
+    while addr < program.len() {
+        if covered.contains_key(&addr) || data.contains(&(addr as i64)) {
+            addr += 1;
+            continue;
+        }
+        let start = addr;
+        let mut last = None;
+        while let Ok(instruction) = Instruction::decode(program, addr) {
+            let cells = addr..addr + instruction.size();
+            if cells
+                .clone()
+                .any(|cell| covered.contains_key(&cell) || data.contains(&(cell as i64)))
+            {
+                break;
+            }
+            last = Some(instruction);
+            addr = cells.end;
+        }
+        match last {
+            Some(Instruction::JumpIfTrue(..))
+            | Some(Instruction::JumpIfFalse(..))
+            | Some(Instruction::Halt) => warnings.push(Warning {
+                addr: start,
+                kind: "unreachable-code",
+                message: format!("Code at {}..{} is never reached", start, addr),
+            }),
+            _ => addr = start + 1,
+        }
+    }
+    warnings
+}
+
+pub fn lint(program: &[i64], target: &[i64]) -> Vec<Warning> {
+    let instructions: Vec<Reached> = reachable(program)
+        .into_iter()
+        // Everything reachable decodes, the walk stops at anything that doesn't
+        .map(|addr| Reached {
+            addr,
+            instruction: Instruction::decode_lenient(program, addr).unwrap(),
+        })
+        .collect();
+    let mut warnings = Vec::new();
+    let mut warn = |addr, kind, message| {
+        warnings.push(Warning {
+            addr,
+            kind,
+            message,
+        })
+    };
+
+    // Which instruction each cell of code belongs to
+    let mut covered = BTreeMap::new();
+    let mut written = BTreeSet::new();
+    let mut data = BTreeSet::new();
+    for instruction in &instructions {
+        for cell in instruction.cells() {
+            covered.insert(cell, instruction.addr);
+        }
+        let (reads, write) = instruction.accesses();
+        written.extend(write);
+        data.extend(reads);
+        data.extend(write);
+    }
+
+    let mut unknown = BTreeSet::new();
+    for instruction in &instructions {
+        let addr = instruction.addr;
+        let op = instruction.instruction.op();
+        if !target.contains(&op) {
+            warn(
+                addr,
+                "unsupported-opcode",
+                format!(
+                    "{} ({}) isn't supported by the target",
+                    instruction.instruction.mnemonic(),
+                    op
+                ),
+            );
+        }
+        if let Err(message) = check_modes(program[addr]) {
+            // With valid modes, the only thing left to be wrong is an immediate mode write
+            let kind = if modes(program[addr]).is_ok() {
+                "immediate-write"
+            } else {
+                "invalid-mode"
+            };
+            warn(addr, kind, message);
+        }
+
+        for successor in instruction.successors() {
+            if successor < 0 || successor as usize >= program.len() {
+                if instruction.is_jump() {
+                    warn(
+                        addr,
+                        "jump-outside-program",
+                        format!("Jumps to {}, outside the program", successor),
+                    );
+                }
+            } else if !covered.contains_key(&(successor as usize)) {
+                unknown.insert(successor as usize);
+            }
+        }
+
+        let (reads, write) = instruction.accesses();
+        for &read in &reads {
+            if read >= program.len() as i64 && !written.contains(&read) {
+                warn(
+                    addr,
+                    "uninitialized-read",
+                    format!(
+                        "Reads {}, past the end of the program and never written",
+                        read
+                    ),
+                );
+            }
+        }
+        for cell in reads.iter().chain(&write) {
+            if *cell < 0 {
+                warn(
+                    addr,
+                    "invalid-address",
+                    format!("Accesses negative address {}", cell),
+                );
+            }
+        }
+    }
+
+    // Whether execution can get from one instruction to another, going by successors
+    let successors: BTreeMap<usize, Vec<usize>> = instructions
+        .iter()
+        .map(|instruction| {
+            let next = instruction.successors().into_iter();
+            let next = next.filter(|&addr| addr >= 0).map(|addr| addr as usize);
+            (instruction.addr, next.collect())
+        })
+        .collect();
+    let leads_to = |from: usize, to: usize| {
+        let mut seen = BTreeSet::new();
+        let mut pending = successors[&from].clone();
+        while let Some(addr) = pending.pop() {
+            if addr == to {
+                return true;
+            }
+            if seen.insert(addr) {
+                pending.extend(successors.get(&addr).into_iter().flatten());
+            }
+        }
+        false
+    };
+
+    // Writes to code that can run after the write, including cells the walk reached but
+    // couldn't decode, since those are usually instructions the program writes for itself
+    let mut self_modifying = false;
+    for instruction in &instructions {
+        let cell = match instruction.accesses().1 {
+            Some(cell) if cell >= 0 => cell as usize,
+            _ => continue,
+        };
+        let owner = covered
+            .get(&cell)
+            .copied()
+            .or_else(|| unknown.get(&cell).copied());
+        if let Some(owner) = owner.filter(|&owner| leads_to(instruction.addr, owner)) {
+            self_modifying = true;
+            warn(
+                instruction.addr,
+                "self-modifying",
+                format!("Writes to {}, part of the instruction at {}", cell, owner),
+            );
+        }
+    }
+
+    for &addr in &unknown {
+        let op = program[addr] % 100;
+        if !target.contains(&op) && !written.contains(&(addr as i64)) {
+            warn(
+                addr,
+                "unsupported-opcode",
+                format!("Reaches {}, which isn't an instruction", program[addr]),
+            );
+        }
+    }
+
+    // Once the program rewrites its own code there's no telling what it reaches
+    if !self_modifying {
+        warnings.extend(unreachable_code(program, &covered, &data));
+    }
+    warnings.sort_by_key(|warning| warning.addr);
+    warnings
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.addr, self.message, self.kind)
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// One JSON object per line, for tools
+pub fn to_json(warnings: &[Warning]) -> String {
+    warnings
+        .iter()
+        .map(|warning| {
+            format!(
+                "{{\"addr\":{},\"kind\":{},\"message\":{}}}\n",
+                warning.addr,
+                json_string(warning.kind),
+                json_string(&warning.message)
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn can_lint_programs() {
+    let load = |day| super::loader::load_program(&format!("res/day_{}.txt", day)).unwrap();
+    assert_eq!(lint(&load("02"), DAY_02), vec![]);
+    assert_eq!(lint(&load("09"), DAY_09), vec![]);
+    assert_eq!(
+        lint(&load("05"), DAY_05),
+        vec![Warning {
+            addr: 2,
+            kind: "self-modifying",
+            message: "Writes to 6, part of the instruction at 6".to_string()
+        }]
+    );
+
+    #[rustfmt::skip]
+    let program = vec![
+        109, 1,                 //  0: arb 1
+        11101, 1, 1, 16,        //  2: add 1, 1, 16
+        1, 500, 0, 17,          //  6: add [500], [0], [17]
+        1006, 17, 40,           // 10: jf [17], 40
+        104, 7,                 // 13: out 7
+        99,                     // 15: hlt
+        0, 0,                   // 16
+        104, 1, 99,             // 18: out 1; hlt
+    ];
+    let warnings = lint(&program, DAY_05);
+    let text: Vec<String> = warnings.iter().map(|w| w.to_string()).collect();
+    assert_eq!(
+        text,
+        vec![
+            "0: arb (9) isn't supported by the target [unsupported-opcode]",
+            "2: Immediate mode write to parameter 3 of 11101 [immediate-write]",
+            "6: Reads 500, past the end of the program and never written [uninitialized-read]",
+            "10: Jumps to 40, outside the program [jump-outside-program]",
+            "18: Code at 18..21 is never reached [unreachable-code]",
+        ]
+    );
+    assert_eq!(
+        to_json(&warnings[3..4]),
+        "{\"addr\":10,\"kind\":\"jump-outside-program\",\"message\":\"Jumps to 40, outside the program\"}\n"
+    );
+
+    // Extensions add to the instruction set
+    let program = vec![1105, 1, 3, 42];
+    assert_eq!(
+        lint(&program, DAY_09)[0].to_string(),
+        "3: Reaches 42, which isn't an instruction [unsupported-opcode]"
+    );
+    assert_eq!(lint(&program, &[5, 42, 99]), vec![]);
+}
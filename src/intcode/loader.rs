@@ -0,0 +1,245 @@
+use std::fs;
+
+// Binary programs are this header followed by one zigzag LEB128 varint per cell
+pub const BINARY_MAGIC: &[u8] = b"ICB\x01";
+
+pub fn load_program(filename: &str) -> Result<Vec<i64>, String> {
+    let bytes =
+        fs::read(filename).map_err(|e| format!("Could not read file {}: {}", filename, e))?;
+    parse_program(&bytes).map_err(|e| format!("Could not load {}: {}", filename, e))
+}
+
+pub fn parse_program(bytes: &[u8]) -> Result<Vec<i64>, String> {
+    if bytes.starts_with(BINARY_MAGIC) {
+        return parse_binary(bytes);
+    }
+    let input = std::str::from_utf8(bytes)
+        .map_err(|e| format!("Invalid UTF-8 at byte offset {} (token 0)", e.valid_up_to()))?;
+    match input.trim_start().chars().next() {
+        Some('[') => parse_json(input),
+        _ => parse_text(input),
+    }
+}
+
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    comments: bool,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str, comments: bool) -> Self {
+        Scanner {
+            bytes: input.as_bytes(),
+            pos: 0,
+            comments,
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_blanks(&mut self) {
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else if self.comments && b == b'#' {
+                while let Some(b) = self.peek() {
+                    if b == b'\n' {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn error(&self, message: &str, token: usize) -> String {
+        format!("{} at byte offset {} (token {})", message, self.pos, token)
+    }
+
+    fn number(&mut self, token: usize) -> Result<i64, String> {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if b.is_ascii_whitespace() || b == b',' || b == b']' || (self.comments && b == b'#') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.bytes[start..self.pos]);
+        if text.is_empty() {
+            return Err(self.error("Expected a number", token));
+        }
+        text.parse().map_err(|_| {
+            format!(
+                "Invalid number {:?} at byte offset {} (token {})",
+                text, start, token
+            )
+        })
+    }
+}
+
+// Comma separated values, whitespace and newlines are ignored and # starts a comment
+pub fn parse_text(input: &str) -> Result<Vec<i64>, String> {
+    let mut scanner = Scanner::new(input, true);
+    let mut program = Vec::new();
+    scanner.skip_blanks();
+    if scanner.peek().is_none() {
+        return Ok(program);
+    }
+    loop {
+        scanner.skip_blanks();
+        program.push(scanner.number(program.len())?);
+        scanner.skip_blanks();
+        match scanner.peek() {
+            None => return Ok(program),
+            Some(b',') => scanner.pos += 1,
+            Some(_) => return Err(scanner.error("Expected ','", program.len())),
+        }
+    }
+}
+
+pub fn parse_json(input: &str) -> Result<Vec<i64>, String> {
+    let mut scanner = Scanner::new(input, false);
+    let mut program = Vec::new();
+    scanner.skip_blanks();
+    if scanner.peek() != Some(b'[') {
+        return Err(scanner.error("Expected '['", 0));
+    }
+    scanner.pos += 1;
+    scanner.skip_blanks();
+    if scanner.peek() == Some(b']') {
+        scanner.pos += 1;
+    } else {
+        loop {
+            scanner.skip_blanks();
+            program.push(scanner.number(program.len())?);
+            scanner.skip_blanks();
+            match scanner.peek() {
+                Some(b',') => scanner.pos += 1,
+                Some(b']') => {
+                    scanner.pos += 1;
+                    break;
+                }
+                _ => return Err(scanner.error("Expected ',' or ']'", program.len())),
+            }
+        }
+    }
+    scanner.skip_blanks();
+    if scanner.peek().is_some() {
+        return Err(scanner.error("Unexpected data after ']'", program.len()));
+    }
+    Ok(program)
+}
+
+pub fn encode_binary(program: &[i64]) -> Vec<u8> {
+    let mut bytes = BINARY_MAGIC.to_vec();
+    for &n in program {
+        let mut zigzag = ((n << 1) ^ (n >> 63)) as u64;
+        loop {
+            let byte = (zigzag & 0x7f) as u8;
+            zigzag >>= 7;
+            if zigzag == 0 {
+                bytes.push(byte);
+                break;
+            }
+            bytes.push(byte | 0x80);
+        }
+    }
+    bytes
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<i64>, String> {
+    if !bytes.starts_with(BINARY_MAGIC) {
+        return Err("Missing binary header at byte offset 0 (token 0)".to_string());
+    }
+    let mut program = Vec::new();
+    let mut pos = BINARY_MAGIC.len();
+    while pos < bytes.len() {
+        let start = pos;
+        let mut zigzag: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = match bytes.get(pos) {
+                Some(b) => *b,
+                None => {
+                    return Err(format!(
+                        "Truncated value at byte offset {} (token {})",
+                        start,
+                        program.len()
+                    ))
+                }
+            };
+            if shift > 63 || (shift == 63 && byte & 0x7e != 0) {
+                return Err(format!(
+                    "Value too large at byte offset {} (token {})",
+                    start,
+                    program.len()
+                ));
+            }
+            zigzag |= u64::from(byte & 0x7f) << shift;
+            shift += 7;
+            pos += 1;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        program.push((zigzag >> 1) as i64 ^ -((zigzag & 1) as i64));
+    }
+    Ok(program)
+}
+
+#[test]
+fn can_parse_text_programs() {
+    assert_eq!(parse_text("1,0,0,0,99\n"), Ok(vec![1, 0, 0, 0, 99]));
+    assert_eq!(
+        parse_text("# add\n1, 0,0, 0 # in place\n,\n  99 # halt\n"),
+        Ok(vec![1, 0, 0, 0, 99])
+    );
+    assert_eq!(parse_text("  \n# nothing\n"), Ok(vec![]));
+    assert_eq!(
+        parse_text("1,0,x0,99"),
+        Err("Invalid number \"x0\" at byte offset 4 (token 2)".to_string())
+    );
+    assert_eq!(
+        parse_text("1,,99"),
+        Err("Expected a number at byte offset 2 (token 1)".to_string())
+    );
+    assert_eq!(
+        parse_text("1,0 0"),
+        Err("Expected ',' at byte offset 4 (token 2)".to_string())
+    );
+}
+
+#[test]
+fn can_parse_json_programs() {
+    assert_eq!(parse_json(" [1, -2,\n 99] \n"), Ok(vec![1, -2, 99]));
+    assert_eq!(parse_json("[]"), Ok(vec![]));
+    assert_eq!(
+        parse_json("[1, 2"),
+        Err("Expected ',' or ']' at byte offset 5 (token 2)".to_string())
+    );
+    assert_eq!(
+        parse_json("[1] 2"),
+        Err("Unexpected data after ']' at byte offset 4 (token 1)".to_string())
+    );
+    assert_eq!(
+        parse_program(b"[1,2.5]"),
+        Err("Invalid number \"2.5\" at byte offset 3 (token 1)".to_string())
+    );
+}
+
+#[test]
+fn can_round_trip_binary_programs() {
+    let program = vec![0, 1, -1, 99, 1_125_899_906_842_624, i64::MIN, i64::MAX];
+    let bytes = encode_binary(&program);
+    assert_eq!(&bytes[..4], BINARY_MAGIC);
+    assert_eq!(parse_program(&bytes), Ok(program));
+    assert_eq!(
+        parse_binary(&bytes[..bytes.len() - 1]),
+        Err("Truncated value at byte offset 27 (token 6)".to_string())
+    );
+}
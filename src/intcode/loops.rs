@@ -0,0 +1,172 @@
+use super::{Intcode, IntcodeState, PagedMemory};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
+use std::hash::{Hash, Hasher};
+
+// Looks for the machine's whole state (memory, ip, rb and pending input) repeating exactly,
+// which means it can never halt. The state is sampled every `interval` instructions and
+// compared against checkpoints taken at 1, 2, 4, 8... samples (Brent's algorithm), so any
+// loop is caught within a small multiple of its length without keeping a history. Consuming
+// input starts the search over. Mapped devices and extensions have state of their own that
+// can't be seen from here, so machines with either are never reported as looping.
+
+#[derive(Clone)]
+struct Snapshot {
+    hash: u64,
+    ip: usize,
+    rb: isize,
+    memory: PagedMemory,
+    input: VecDeque<i64>,
+}
+
+#[derive(Clone)]
+pub(super) struct LoopDetector {
+    interval: u64,
+    until_sample: u64,
+    samples: u64,
+    next_checkpoint: u64,
+    checkpoint: Option<Snapshot>,
+}
+
+impl LoopDetector {
+    pub(super) fn reset(&mut self) {
+        self.until_sample = self.interval;
+        self.samples = 0;
+        self.next_checkpoint = 1;
+        self.checkpoint = None;
+    }
+}
+
+impl Intcode {
+    pub fn enable_loop_detection(&mut self, interval: u64) {
+        let mut detector = LoopDetector {
+            interval: interval.max(1),
+            until_sample: 0,
+            samples: 0,
+            next_checkpoint: 1,
+            checkpoint: None,
+        };
+        detector.reset();
+        self.loop_detector = Some(detector);
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.memory.hash(&mut hasher);
+        self.ip.hash(&mut hasher);
+        self.rb.hash(&mut hasher);
+        self.input_queue.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn snapshot(&self, hash: u64) -> Snapshot {
+        Snapshot {
+            hash,
+            ip: self.ip,
+            rb: self.rb,
+            memory: self.memory.clone(),
+            input: self.input_queue.clone(),
+        }
+    }
+
+    fn matches(&self, snapshot: &Snapshot, hash: u64) -> bool {
+        snapshot.hash == hash
+            && snapshot.ip == self.ip
+            && snapshot.rb == self.rb
+            && snapshot.input == self.input_queue
+            && snapshot.memory == self.memory
+    }
+
+    // Called after every instruction while detection is enabled
+    pub(super) fn check_for_loop(&mut self) -> Result<(), String> {
+        if !self.devices.is_empty() || !self.extensions.is_empty() {
+            return Ok(());
+        }
+        // Taken out while sampling, which also keeps it off the copy that measures a loop
+        let mut detector = match self.loop_detector.take() {
+            Some(detector) => detector,
+            None => return Ok(()),
+        };
+        let result = self.sample(&mut detector);
+        self.loop_detector = Some(detector);
+        result
+    }
+
+    fn sample(&mut self, detector: &mut LoopDetector) -> Result<(), String> {
+        detector.until_sample -= 1;
+        if detector.until_sample > 0 {
+            return Ok(());
+        }
+        detector.until_sample = detector.interval;
+        detector.samples += 1;
+
+        let hash = self.state_hash();
+        if let Some(checkpoint) = &detector.checkpoint {
+            if self.matches(checkpoint, hash) {
+                return self.measure_loop(hash);
+            }
+        }
+        if detector.samples == detector.next_checkpoint {
+            detector.checkpoint = Some(self.snapshot(hash));
+            detector.next_checkpoint *= 2;
+        }
+        Ok(())
+    }
+
+    // The current state is known to come round again, so stepping until it does gives the
+    // exact period. That's done on a copy nobody is watching, so the machine's outputs,
+    // recording, observers and cycle count are left as they were.
+    fn measure_loop(&mut self, hash: u64) -> Result<(), String> {
+        let found = self.snapshot(hash);
+        let mut copy = self.clone();
+        copy.observers.clear();
+        copy.recording = None;
+        copy.call_stack = None;
+        let mut start = copy.ip;
+        let mut period = 0;
+        loop {
+            copy.compute_next_op()?;
+            period += 1;
+            start = start.min(copy.ip);
+            if copy.matches(&found, copy.state_hash()) {
+                break;
+            }
+        }
+        self.state = IntcodeState::Looping { start, period };
+        Ok(())
+    }
+}
+
+#[test]
+fn can_detect_infinite_loops() {
+    // eq [10], 0, [10]; jt 1, 0   (flipping the cell at 10 forever)
+    let program = vec![1008, 10, 0, 10, 1105, 1, 0, 0, 0, 0, 0];
+    for interval in &[1, 3, 100] {
+        let mut ic = Intcode::new(program.clone(), false);
+        ic.enable_loop_detection(*interval);
+        ic.progress_program().unwrap();
+        assert_eq!(
+            ic.get_state(),
+            IntcodeState::Looping {
+                start: 0,
+                period: 4
+            }
+        );
+        assert_eq!(ic.step(), Ok(ic.get_state()));
+    }
+
+    // out 1; jt 1, 0   (only what ran before the loop was spotted shows)
+    let mut ic = Intcode::new(vec![104, 1, 1105, 1, 0], false);
+    ic.enable_loop_detection(1);
+    ic.enable_recording();
+    ic.progress_program().unwrap();
+    assert_eq!(ic.cycles(), 4);
+    assert_eq!(ic.recording().unwrap().len(), 2);
+    assert_eq!(std::iter::from_fn(|| ic.dequeue_output()).count(), 2);
+
+    // add [9], -1, [9]; jt [9], 0; hlt   (counting down from 1000 takes a while, but halts)
+    let mut ic = Intcode::new(vec![1001, 9, -1, 9, 1005, 9, 0, 99, 0, 1000], false);
+    ic.enable_loop_detection(1);
+    ic.progress_program().unwrap();
+    assert_eq!(ic.get_state(), IntcodeState::Done);
+}
@@ -0,0 +1,206 @@
+use super::pages::footprint;
+use super::Intcode;
+use std::ops::Range;
+
+// These go straight to memory, bypassing any mapped devices, so inspecting a machine never
+// has side effects on it.
+
+#[derive(Debug, PartialEq)]
+pub struct MemoryChange {
+    pub addr: usize,
+    pub before: i64,
+    pub after: i64,
+}
+
+#[derive(Clone, Copy)]
+pub enum Radix {
+    Decimal,
+    Hex,
+}
+
+const DUMP_WIDTH: usize = 8;
+
+// Bytes of memory held by a group of machines, counting the pages forks share only once
+pub fn memory_footprint<'a>(machines: impl IntoIterator<Item = &'a Intcode>) -> usize {
+    footprint(machines.into_iter().map(|ic| &ic.memory))
+}
+
+impl Intcode {
+    fn check_range(&self, range: &Range<usize>) -> Result<(), String> {
+        if range.start > range.end || range.end > self.memory.len() {
+            return Err(format!(
+                "Address range out of bounds: {:?} (memory size {})",
+                range,
+                self.memory.len()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, addr: usize) -> Result<i64, String> {
+        self.memory.get(addr).ok_or_else(|| {
+            format!(
+                "Address out of bounds: {} (memory size {})",
+                addr,
+                self.memory.len()
+            )
+        })
+    }
+
+    pub fn write(&mut self, addr: usize, val: i64) -> Result<(), String> {
+        self.write_range(addr, &[val])
+    }
+
+    pub fn read_range(&self, range: Range<usize>) -> Result<Vec<i64>, String> {
+        self.check_range(&range)?;
+        Ok(self.memory.range(range))
+    }
+
+    pub fn write_range(&mut self, start: usize, values: &[i64]) -> Result<(), String> {
+        let end = start.checked_add(values.len()).ok_or_else(|| {
+            format!(
+                "Address range overflows: {} cells from {}",
+                values.len(),
+                start
+            )
+        })?;
+        let range = start..end;
+        self.check_range(&range)?;
+        for (addr, val) in range.zip(values) {
+            self.memory[addr] = *val;
+        }
+        Ok(())
+    }
+
+    pub fn memory_size(&self) -> usize {
+        self.memory.len()
+    }
+
+    // Every cell that differs from the image the machine was created with
+    pub fn diff(&self) -> Vec<MemoryChange> {
+        self.memory
+            .changes_from(&self.initial)
+            .into_iter()
+            .map(|(addr, before, after)| MemoryChange {
+                addr,
+                before,
+                after,
+            })
+            .collect()
+    }
+
+    pub fn dump(&self, range: Range<usize>, radix: Radix) -> Result<String, String> {
+        self.check_range(&range)?;
+        let cells: Vec<String> = self
+            .memory
+            .range(range.clone())
+            .iter()
+            .map(|n| match radix {
+                Radix::Decimal => n.to_string(),
+                Radix::Hex if *n < 0 => format!("-{:x}", n.unsigned_abs()),
+                Radix::Hex => format!("{:x}", n),
+            })
+            .collect();
+        let width = cells.iter().map(|c| c.len()).max().unwrap_or(0);
+
+        let mut table = String::new();
+        for (row, chunk) in cells.chunks(DUMP_WIDTH).enumerate() {
+            let addr = range.start + row * DUMP_WIDTH;
+            let label = match radix {
+                Radix::Decimal => format!("{:>6}", addr),
+                Radix::Hex => format!("{:>6x}", addr),
+            };
+            let values: Vec<String> = chunk
+                .iter()
+                .map(|c| format!("{:>w$}", c, w = width))
+                .collect();
+            table += &format!("{} | {}\n", label, values.join(" "));
+        }
+        Ok(table)
+    }
+
+    pub fn rb(&self) -> isize {
+        self.rb
+    }
+
+    pub fn set_ip(&mut self, ip: usize) -> Result<(), String> {
+        if ip >= self.memory.len() {
+            return Err(format!(
+                "Address out of bounds: {} (memory size {})",
+                ip,
+                self.memory.len()
+            ));
+        }
+        self.ip = ip;
+        Ok(())
+    }
+
+    pub fn set_rb(&mut self, rb: isize) {
+        self.rb = rb;
+    }
+}
+
+#[test]
+fn can_read_and_write_memory() {
+    let mut ic = Intcode::new(vec![1, 0, 0, 0, 99], false);
+    assert_eq!(ic.read(4), Ok(99));
+    assert_eq!(
+        ic.read(5),
+        Err("Address out of bounds: 5 (memory size 5)".to_string())
+    );
+    ic.write(1, 4).unwrap();
+    ic.write_range(2, &[4, 3]).unwrap();
+    assert_eq!(ic.read_range(0..5), Ok(vec![1, 4, 4, 3, 99]));
+    assert_eq!(
+        ic.write_range(4, &[1, 2]),
+        Err("Address range out of bounds: 4..6 (memory size 5)".to_string())
+    );
+    assert_eq!(
+        ic.write_range(usize::MAX, &[1, 2]),
+        Err(format!(
+            "Address range overflows: 2 cells from {}",
+            usize::MAX
+        ))
+    );
+
+    ic.progress_program().unwrap();
+    assert_eq!(ic.read(3), Ok(198));
+    assert_eq!(
+        ic.diff(),
+        vec![
+            MemoryChange {
+                addr: 1,
+                before: 0,
+                after: 4
+            },
+            MemoryChange {
+                addr: 2,
+                before: 0,
+                after: 4
+            },
+            MemoryChange {
+                addr: 3,
+                before: 0,
+                after: 198
+            }
+        ]
+    );
+}
+
+#[test]
+fn can_dump_memory_and_set_registers() {
+    let mut ic = Intcode::new((0..10).map(|n| n * 100 - 300).collect(), false);
+    assert_eq!(
+        ic.dump(0..10, Radix::Decimal),
+        Ok("     0 | -300 -200 -100    0  100  200  300  400\n     8 |  500  600\n".to_string())
+    );
+    assert_eq!(
+        ic.dump(7..10, Radix::Hex),
+        Ok("     7 | 190 1f4 258\n".to_string())
+    );
+
+    ic.set_ip(9).unwrap();
+    ic.set_rb(-2);
+    assert_eq!((ic.ip(), ic.rb()), (9, -2));
+    assert!(ic.set_ip(10).is_err());
+}
@@ -0,0 +1,117 @@
+use super::Intcode;
+
+// Observers watch a machine run without being able to change it. Every callback has a do
+// nothing default, so an observer only implements the ones it cares about. Reads and writes
+// are the operands an instruction accesses through an address (position or relative mode),
+// not the instruction words themselves. Like extensions and devices, observers are shared
+// between a machine and its clones.
+pub trait Observer {
+    // Called with ip on an instruction that's about to execute
+    fn before_instruction(&mut self, _ic: &Intcode, _ip: usize) {}
+
+    // Called once the instruction that was at ip has finished
+    fn after_instruction(&mut self, _ic: &Intcode, _ip: usize) {}
+
+    // Called when the conditional jump at ip decides which way to go
+    fn branch(&mut self, _ip: usize, _taken: bool) {}
+
+    fn memory_read(&mut self, _addr: usize, _value: i64) {}
+
+    fn memory_write(&mut self, _addr: usize, _value: i64) {}
+
+    fn input_consumed(&mut self, _value: i64) {}
+
+    fn output_produced(&mut self, _value: i64) {}
+
+    fn halted(&mut self, _ic: &Intcode) {}
+}
+
+impl Intcode {
+    // The machine's own recording and call stack are observers too, told about everything
+    // before the ones that were added
+    pub(super) fn notify(&self, mut event: impl FnMut(&mut dyn Observer)) {
+        if let Some(recording) = &self.recording {
+            event(&mut *recording.borrow_mut());
+        }
+        if let Some(call_stack) = &self.call_stack {
+            event(&mut *call_stack.borrow_mut());
+        }
+        for observer in &self.observers {
+            event(&mut *observer.borrow_mut());
+        }
+    }
+}
+
+#[cfg(test)]
+use std::cell::RefCell;
+#[cfg(test)]
+use std::rc::Rc;
+
+// Stops nothing, but remembers every value written to the addresses it watches
+#[cfg(test)]
+#[derive(Default)]
+struct Watchpoints {
+    watched: Vec<usize>,
+    hits: Vec<(usize, usize, i64)>,
+    ip: usize,
+    events: Vec<String>,
+}
+
+#[cfg(test)]
+impl Observer for Watchpoints {
+    fn before_instruction(&mut self, _ic: &Intcode, ip: usize) {
+        self.ip = ip;
+    }
+
+    fn after_instruction(&mut self, ic: &Intcode, ip: usize) {
+        self.events.push(format!("{} -> {}", ip, ic.ip()));
+    }
+
+    fn memory_write(&mut self, addr: usize, value: i64) {
+        if self.watched.contains(&addr) {
+            self.hits.push((self.ip, addr, value));
+        }
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        self.events.push(format!("in {}", value));
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        self.events.push(format!("out {}", value));
+    }
+
+    fn halted(&mut self, ic: &Intcode) {
+        self.events.push(format!("halt after {}", ic.cycles()));
+    }
+}
+
+#[test]
+fn observers_see_every_event() {
+    // in [9]; add [9], [9], [10]; out [10]; hlt
+    let mut ic = Intcode::new(vec![3, 9, 1, 9, 9, 10, 4, 10, 99, 0, 0], false);
+    let watch = Rc::new(RefCell::new(Watchpoints {
+        watched: vec![10],
+        ..Watchpoints::default()
+    }));
+    let other = Rc::new(RefCell::new(Watchpoints::default()));
+    ic.add_observer(watch.clone());
+    ic.add_observer(other.clone());
+    ic.queue_input(21);
+    ic.progress_program().unwrap();
+
+    assert_eq!(watch.borrow().hits, vec![(2, 10, 42)]);
+    assert_eq!(
+        watch.borrow().events,
+        vec![
+            "in 21",
+            "0 -> 2",
+            "2 -> 6",
+            "out 42",
+            "6 -> 8",
+            "8 -> 8",
+            "halt after 4"
+        ]
+    );
+    assert_eq!(other.borrow().events, watch.borrow().events);
+}
@@ -0,0 +1,352 @@
+use super::instruction::Mode;
+use super::validator::reachable;
+use super::{Instruction, Intcode, IntcodeState, Observer, Param};
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+// A peephole optimizer that rewrites instructions in place. Nothing can be moved, since any
+// cell might be an address, so every rewrite keeps the instruction's length: constant
+// arithmetic becomes `add k, 0, [dst]`, jumps that are always taken become `jt 1, target`,
+// and jumps that are never taken or only go to the next instruction become the no-op
+// below. A no-op still executes, so it only saves anything once the jumps into it are
+// threaded past it. Only reachable instructions are rewritten,
+// and only when none of their cells are ever written or read as data, either by a position
+// mode operand or while running the program on the inputs given. Each rewrite is then
+// checked by running the program on every input and comparing against the original, and
+// dropped if anything differs.
+
+const fn immediate(value: i64) -> Param {
+    Param {
+        mode: Mode::Immediate,
+        value,
+    }
+}
+
+// A jump that's never taken, the same length as the jumps it replaces
+const NOP: Instruction = Instruction::JumpIfTrue(immediate(0), immediate(0));
+
+#[derive(Debug, PartialEq)]
+pub struct Rewrite {
+    pub addr: usize,
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug)]
+pub struct Optimized {
+    pub program: Vec<i64>,
+    pub applied: Vec<Rewrite>,
+    // Rewrites that changed what the program did on one of the inputs
+    pub rejected: Vec<Rewrite>,
+    // Instructions executed over all the inputs, before and after
+    pub cycles: (u64, u64),
+}
+
+// Every cell an instruction reads or writes through an address
+#[derive(Default)]
+struct DataAccesses(BTreeSet<usize>);
+
+impl Observer for DataAccesses {
+    fn memory_read(&mut self, addr: usize, _value: i64) {
+        self.0.insert(addr);
+    }
+
+    fn memory_write(&mut self, addr: usize, _value: i64) {
+        self.0.insert(addr);
+    }
+}
+
+#[derive(PartialEq)]
+struct Run {
+    outputs: Vec<i64>,
+    state: Result<IntcodeState, String>,
+    cycles: u64,
+}
+
+// Runs until the program halts, fails, wants more input than it was given, or has executed
+// more than budget instructions
+fn run(
+    program: &[i64],
+    input: &[i64],
+    budget: u64,
+    accesses: Option<Rc<RefCell<DataAccesses>>>,
+) -> Run {
+    let mut ic = Intcode::new(program.to_vec(), true);
+    if let Some(accesses) = accesses {
+        ic.add_observer(accesses);
+    }
+    input.iter().for_each(|&i| ic.queue_input(i));
+    let state = loop {
+        match ic.step() {
+            Ok(IntcodeState::Running) if ic.cycles() > budget => {
+                break Err(format!("Ran for more than {} instructions", budget))
+            }
+            Ok(IntcodeState::Running) => {}
+            result => break result,
+        }
+    };
+    Run {
+        outputs: std::iter::from_fn(|| ic.dequeue_output()).collect(),
+        state,
+        cycles: ic.cycles(),
+    }
+}
+
+// The parameter's value, if it's immediate
+fn constant(param: Param) -> Option<i64> {
+    match param.mode {
+        Mode::Immediate => Some(param.value),
+        _ => None,
+    }
+}
+
+fn fold(instruction: Instruction) -> Option<Instruction> {
+    use Instruction::*;
+    match instruction {
+        Add(a, b, dest) | Mul(a, b, dest) | LessThan(a, b, dest) | Equals(a, b, dest) => {
+            let (a, b) = (constant(a)?, constant(b)?);
+            let value = match instruction {
+                Add(..) => a.checked_add(b)?,
+                Mul(..) => a.checked_mul(b)?,
+                LessThan(..) => i64::from(a < b),
+                _ => i64::from(a == b),
+            };
+            Some(Add(immediate(value), immediate(0), dest))
+        }
+        JumpIfTrue(_, target) | JumpIfFalse(_, target) => match instruction.taken()? {
+            true => Some(JumpIfTrue(immediate(1), target)),
+            false => Some(NOP),
+        },
+        _ => None,
+    }
+}
+
+// Turns a jump whose target is the next instruction into a no-op, which costs the same but
+// lets the jumps that land on it be threaded through
+fn nop_jump_to_next(addr: usize, instruction: Instruction) -> Option<Instruction> {
+    match instruction {
+        Instruction::JumpIfTrue(_, target) | Instruction::JumpIfFalse(_, target)
+            if constant(target)? == (addr + instruction.size()) as i64 =>
+        {
+            Some(NOP)
+        }
+        _ => None,
+    }
+}
+
+struct Optimizer<'a> {
+    instructions: BTreeSet<usize>,
+    accessed: &'a BTreeSet<usize>,
+}
+
+impl Optimizer<'_> {
+    fn can_rewrite(&self, program: &[i64], addr: usize, instruction: Instruction) -> bool {
+        let end = addr + instruction.size();
+        end <= program.len()
+            && (addr..end).all(|cell| !self.accessed.contains(&cell))
+            && (addr + 1..end).all(|cell| !self.instructions.contains(&cell))
+    }
+
+    // Follows a chain of jumps that are always taken, and no-ops, from target
+    fn thread(&self, program: &[i64], instruction: Instruction) -> Option<Instruction> {
+        let (cond, start) = match instruction {
+            Instruction::JumpIfTrue(cond, target) | Instruction::JumpIfFalse(cond, target) => {
+                (cond, constant(target)?)
+            }
+            _ => return None,
+        };
+        let mut target = start;
+        let mut seen = BTreeSet::new();
+        while target >= 0 && seen.insert(target) {
+            let next = target as usize;
+            let jump = match Instruction::decode(program, next) {
+                Ok(jump) if self.instructions.contains(&next) => jump,
+                _ => break,
+            };
+            if !self.can_rewrite(program, next, jump) {
+                break;
+            }
+            target = match (jump, jump.taken()) {
+                (Instruction::JumpIfTrue(_, to), Some(true))
+                | (Instruction::JumpIfFalse(_, to), Some(true)) => match constant(to) {
+                    Some(to) => to,
+                    None => break,
+                },
+                (_, Some(false)) => (next + jump.size()) as i64,
+                _ => break,
+            };
+        }
+        if target == start {
+            return None;
+        }
+        let target = immediate(target);
+        Some(match instruction {
+            Instruction::JumpIfTrue(..) => Instruction::JumpIfTrue(cond, target),
+            _ => Instruction::JumpIfFalse(cond, target),
+        })
+    }
+}
+
+// Optimizes program, checking every rewrite against the inputs given (one run per input).
+// Fails if the original program fails on any of them.
+pub fn optimize(program: &[i64], inputs: &[Vec<i64>]) -> Result<Optimized, String> {
+    let accessed = Rc::new(RefCell::new(DataAccesses::default()));
+    let mut expected = Vec::new();
+    for (i, input) in inputs.iter().enumerate() {
+        let run = run(program, input, u64::MAX, Some(accessed.clone()));
+        if let Err(e) = &run.state {
+            return Err(format!("The program fails on input {}: {}", i, e));
+        }
+        expected.push(run);
+    }
+
+    let mut accessed = accessed.borrow().0.clone();
+    for &addr in &reachable(program) {
+        let instruction = Instruction::decode_lenient(program, addr).unwrap();
+        for param in instruction.params() {
+            if param.mode == Mode::Position && param.value >= 0 {
+                accessed.insert(param.value as usize);
+            }
+        }
+    }
+
+    let mut optimized = Optimized {
+        program: program.to_vec(),
+        applied: Vec::new(),
+        rejected: Vec::new(),
+        cycles: (expected.iter().map(|run| run.cycles).sum(), 0),
+    };
+    for pass in 0..3 {
+        let optimizer = Optimizer {
+            instructions: reachable(&optimized.program).into_iter().collect(),
+            accessed: &accessed,
+        };
+        for &addr in &optimizer.instructions {
+            let current = &optimized.program;
+            // Only well formed instructions are rewritten
+            let instruction = match Instruction::decode(current, addr) {
+                Ok(instruction) if optimizer.can_rewrite(current, addr, instruction) => instruction,
+                _ => continue,
+            };
+            let rewritten = match pass {
+                0 => fold(instruction),
+                1 => nop_jump_to_next(addr, instruction),
+                _ => optimizer.thread(current, instruction),
+            };
+            let rewritten = match rewritten {
+                Some(rewritten) if rewritten != instruction => rewritten,
+                _ => continue,
+            };
+
+            let mut candidate = current.clone();
+            candidate[addr..addr + rewritten.size()].copy_from_slice(&rewritten.encode());
+            let rewrite = Rewrite {
+                addr,
+                before: instruction.to_string(),
+                after: rewritten.to_string(),
+            };
+            let same = inputs.iter().zip(&expected).all(|(input, expected)| {
+                let run = run(&candidate, input, expected.cycles, None);
+                run.outputs == expected.outputs && run.state == expected.state
+            });
+            if same {
+                optimized.program = candidate;
+                optimized.applied.push(rewrite);
+            } else {
+                optimized.rejected.push(rewrite);
+            }
+        }
+    }
+
+    optimized.cycles.1 = inputs
+        .iter()
+        .map(|input| run(&optimized.program, input, u64::MAX, None).cycles)
+        .sum();
+    Ok(optimized)
+}
+
+#[test]
+fn can_fold_nop_and_thread_jumps() {
+    let program = super::assembler::assemble(
+        "test",
+        "
+                add 2, 3, [x]           # folds
+                jt 1, next              # only goes to the next instruction
+        next:   jt [x], hop             # threads through hop
+                hlt
+        hop:    jt 1, end
+                hlt
+        end:    mul 6, 7, [patched+1]   # folds
+        patched: add 1, 1, [x]          # modified by the line above, so left alone
+                out [x]
+                hlt
+        x:      .data 0
+        ",
+    )
+    .unwrap()
+    .code;
+    let optimized = optimize(&program, &[vec![]]).unwrap();
+    let applied: Vec<String> = optimized
+        .applied
+        .iter()
+        .map(|r| format!("{}: {} -> {}", r.addr, r.before, r.after))
+        .collect();
+    assert_eq!(
+        applied,
+        vec![
+            "0: add 2, 3, [26] -> add 5, 0, [26]",
+            "15: mul 6, 7, [20] -> add 42, 0, [20]",
+            "4: jt 1, 7 -> jt 0, 0",
+            "7: jt [26], 11 -> jt [26], 15",
+        ]
+    );
+    assert!(optimized.rejected.is_empty());
+    assert_eq!(optimized.cycles, (8, 7));
+
+    let outputs: Result<Vec<i64>, String> = Intcode::run_iter(optimized.program, vec![]).collect();
+    assert_eq!(outputs, Ok(vec![43]));
+
+    let day_05 = super::loader::load_program("res/day_05.txt").unwrap();
+    let optimized = optimize(&day_05, &[vec![1], vec![5]]).unwrap();
+    assert!(optimized.rejected.is_empty());
+    for input in 1..=5 {
+        let before: Vec<_> = Intcode::run_iter(day_05.clone(), vec![input]).collect();
+        let after: Vec<_> = Intcode::run_iter(optimized.program.clone(), vec![input]).collect();
+        assert_eq!(before, after, "input {}", input);
+    }
+}
+
+#[test]
+fn jumps_are_threaded_past_jumps_to_the_next_instruction() {
+    let program = super::assembler::assemble(
+        "test",
+        "
+                jt [x], a               # threads through a and b
+                hlt
+        a:      jt [x], b               # only goes to the next instruction
+        b:      jf [x], c               # and so does this one
+        c:      out [x]
+                hlt
+        x:      .data 1
+        ",
+    )
+    .unwrap()
+    .code;
+    let optimized = optimize(&program, &[vec![]]).unwrap();
+    let applied: Vec<String> = optimized
+        .applied
+        .iter()
+        .map(|r| format!("{}: {} -> {}", r.addr, r.before, r.after))
+        .collect();
+    assert_eq!(
+        applied,
+        vec![
+            "4: jt [13], 7 -> jt 0, 0",
+            "7: jf [13], 10 -> jt 0, 0",
+            "0: jt [13], 4 -> jt [13], 10",
+        ]
+    );
+    // The no-ops alone save nothing, skipping them takes two instructions off the run
+    assert_eq!(optimized.cycles, (5, 3));
+}
@@ -0,0 +1,99 @@
+use super::{Intcode, IntcodeState};
+
+// Steps the machine lazily, only as far as the next output, and only pulls the next input
+// when the program actually asks for one. Errors end the iteration, as does running out of
+// input while the program is still waiting on some.
+pub struct Outputs<I> {
+    ic: Intcode,
+    inputs: I,
+    finished: bool,
+}
+
+impl Intcode {
+    pub fn run_iter<I: IntoIterator<Item = i64>>(
+        program: Vec<i64>,
+        inputs: I,
+    ) -> Outputs<I::IntoIter> {
+        Intcode::new(program, true).into_outputs(inputs)
+    }
+
+    // For machines that need setting up before they run
+    pub fn into_outputs<I: IntoIterator<Item = i64>>(self, inputs: I) -> Outputs<I::IntoIter> {
+        Outputs {
+            ic: self,
+            inputs: inputs.into_iter(),
+            finished: false,
+        }
+    }
+}
+
+impl<I> Outputs<I> {
+    pub fn machine(&self) -> &Intcode {
+        &self.ic
+    }
+
+    fn fail(&mut self, message: String) -> Option<Result<i64, String>> {
+        self.finished = true;
+        Some(Err(message))
+    }
+}
+
+impl<I: Iterator<Item = i64>> Iterator for Outputs<I> {
+    type Item = Result<i64, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(o) = self.ic.dequeue_output() {
+                return Some(Ok(o));
+            }
+            if self.finished {
+                return None;
+            }
+            match self.ic.step() {
+                Err(e) => return self.fail(e),
+                Ok(IntcodeState::PollingInput) => match self.inputs.next() {
+                    Some(i) => self.ic.queue_input(i),
+                    None => {
+                        let message = format!("Ran out of input at {}", self.ic.ip());
+                        return self.fail(message);
+                    }
+                },
+                Ok(IntcodeState::Looping { start, period }) => {
+                    let message = format!("Stuck in a loop at {} with period {}", start, period);
+                    return self.fail(message);
+                }
+                Ok(IntcodeState::Done) => self.finished = true,
+                Ok(_) => {}
+            }
+        }
+    }
+}
+
+#[test]
+fn can_iterate_over_outputs() {
+    use std::cell::Cell;
+
+    // in [11]; mul [11], 2, [11]; out [11]; jt 1, 0
+    let doubler = vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 0, 0];
+    let pulled = Cell::new(0);
+    let outputs: Vec<i64> = Intcode::run_iter(
+        doubler.clone(),
+        (1..).inspect(|_| pulled.set(pulled.get() + 1)),
+    )
+    .take(3)
+    .collect::<Result<_, _>>()
+    .unwrap();
+    assert_eq!(outputs, vec![2, 4, 6]);
+    assert_eq!(pulled.get(), 3);
+
+    let outputs: Vec<Result<i64, String>> = Intcode::run_iter(doubler, vec![5]).collect();
+    assert_eq!(
+        outputs,
+        vec![Ok(10), Err("Ran out of input at 0".to_string())]
+    );
+
+    let mut outputs = Intcode::run_iter(vec![104, 7, 104, 8, 99], None);
+    assert_eq!(outputs.next(), Some(Ok(7)));
+    assert_eq!(outputs.machine().ip(), 2);
+    assert_eq!(outputs.collect::<Vec<_>>(), vec![Ok(8)]);
+}
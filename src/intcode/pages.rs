@@ -0,0 +1,147 @@
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::ops::{Index, IndexMut, Range};
+use std::rc::Rc;
+
+// Memory split into fixed size pages that clones share until one of them writes to a page,
+// so forking a machine only copies its page table, and each fork only pays for the pages it
+// goes on to change.
+
+pub const PAGE_SIZE: usize = 256;
+
+#[derive(Clone, Debug)]
+pub struct PagedMemory {
+    pages: Vec<Rc<Vec<i64>>>,
+    len: usize,
+}
+
+impl From<Vec<i64>> for PagedMemory {
+    fn from(cells: Vec<i64>) -> Self {
+        PagedMemory {
+            len: cells.len(),
+            pages: cells
+                .chunks(PAGE_SIZE)
+                .map(|page| Rc::new(page.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl PagedMemory {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, addr: usize) -> Option<i64> {
+        self.pages
+            .get(addr / PAGE_SIZE)
+            .and_then(|page| page.get(addr % PAGE_SIZE))
+            .copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = i64> + '_ {
+        self.pages.iter().flat_map(|page| page.iter().copied())
+    }
+
+    // Panics if the range is out of bounds, like slicing a Vec would
+    pub fn range(&self, range: Range<usize>) -> Vec<i64> {
+        assert!(range.end <= self.len, "range {:?} out of bounds", range);
+        range.map(|addr| self[addr]).collect()
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.iter().collect()
+    }
+
+    // Every cell that differs from base, skipping the pages the two still share
+    pub fn changes_from(&self, base: &PagedMemory) -> Vec<(usize, i64, i64)> {
+        let mut changes = Vec::new();
+        for (i, page) in self.pages.iter().enumerate() {
+            let base_page = base.pages.get(i);
+            if base_page.is_some_and(|base_page| Rc::ptr_eq(page, base_page)) {
+                continue;
+            }
+            for (offset, &after) in page.iter().enumerate() {
+                let before = base_page
+                    .and_then(|base_page| base_page.get(offset))
+                    .copied()
+                    .unwrap_or(0);
+                if before != after {
+                    changes.push((i * PAGE_SIZE + offset, before, after));
+                }
+            }
+        }
+        changes
+    }
+}
+
+// How many bytes of pages a set of memories hold between them, counting shared pages once
+pub fn footprint<'a>(memories: impl IntoIterator<Item = &'a PagedMemory>) -> usize {
+    let mut seen = HashSet::new();
+    memories
+        .into_iter()
+        .flat_map(|memory| memory.pages.iter())
+        .filter(|page| seen.insert(Rc::as_ptr(page)))
+        .map(|page| page.len() * std::mem::size_of::<i64>())
+        .sum()
+}
+
+impl Index<usize> for PagedMemory {
+    type Output = i64;
+
+    fn index(&self, addr: usize) -> &i64 {
+        &self.pages[addr / PAGE_SIZE][addr % PAGE_SIZE]
+    }
+}
+
+impl IndexMut<usize> for PagedMemory {
+    fn index_mut(&mut self, addr: usize) -> &mut i64 {
+        &mut Rc::make_mut(&mut self.pages[addr / PAGE_SIZE])[addr % PAGE_SIZE]
+    }
+}
+
+impl PartialEq for PagedMemory {
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self
+                .pages
+                .iter()
+                .zip(&other.pages)
+                .all(|(a, b)| Rc::ptr_eq(a, b) || a == b)
+    }
+}
+
+impl Hash for PagedMemory {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for page in &self.pages {
+            page.hash(state);
+        }
+    }
+}
+
+#[test]
+fn forks_share_unchanged_pages() {
+    let memory = PagedMemory::from(vec![7; PAGE_SIZE * 4 + 10]);
+    let mut fork = memory.clone();
+    assert_eq!(footprint(vec![&memory, &fork]), (PAGE_SIZE * 4 + 10) * 8);
+
+    fork[PAGE_SIZE + 3] = 1;
+    fork[PAGE_SIZE * 4 + 9] = 2;
+    assert_eq!(footprint(vec![&memory, &fork]), (PAGE_SIZE * 5 + 20) * 8);
+    assert_eq!((memory[PAGE_SIZE + 3], fork[PAGE_SIZE + 3]), (7, 1));
+    assert_eq!(
+        fork.changes_from(&memory),
+        vec![(PAGE_SIZE + 3, 7, 1), (PAGE_SIZE * 4 + 9, 7, 2)]
+    );
+    assert_ne!(fork, memory);
+
+    fork[PAGE_SIZE + 3] = 7;
+    fork[PAGE_SIZE * 4 + 9] = 7;
+    assert_eq!(fork, memory);
+    assert_eq!(fork.get(PAGE_SIZE * 4 + 10), None);
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+// A device gets every read and write to the address range it is mapped to, with the
+// address given as an offset into that range. tick is called once per instruction, after it
+// has finished. A device with a fixed number of cells can't be mapped to a bigger range.
+pub trait Device {
+    fn read(&mut self, offset: usize) -> i64;
+    fn write(&mut self, offset: usize, val: i64);
+    fn tick(&mut self) {}
+    fn size(&self) -> Option<usize> {
+        None
+    }
+}
+
+// Counts instructions executed since it was mapped, writing any value resets it.
+#[derive(Default)]
+pub struct Clock {
+    ticks: i64,
+}
+
+impl Device for Clock {
+    fn read(&mut self, _offset: usize) -> i64 {
+        self.ticks
+    }
+
+    fn write(&mut self, _offset: usize, _val: i64) {
+        self.ticks = 0;
+    }
+
+    fn tick(&mut self) {
+        self.ticks += 1;
+    }
+}
+
+// A xorshift generator, every read yields the next non negative number and writing reseeds it.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: i64) -> Self {
+        let mut rng = Self { state: 0 };
+        rng.write(0, seed);
+        rng
+    }
+}
+
+impl Device for Rng {
+    fn read(&mut self, _offset: usize) -> i64 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 7;
+        self.state ^= self.state << 17;
+        (self.state >> 1) as i64
+    }
+
+    fn write(&mut self, _offset: usize, val: i64) {
+        // xorshift gets stuck on a zero state
+        self.state = if val == 0 {
+            0x2545_f491_4f6c_dd1d
+        } else {
+            val as u64
+        };
+    }
+}
+
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<i64>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> i64 {
+        self.pixels[y * self.width + x]
+    }
+}
+
+impl Device for Framebuffer {
+    fn read(&mut self, offset: usize) -> i64 {
+        self.pixels[offset]
+    }
+
+    fn write(&mut self, offset: usize, val: i64) {
+        self.pixels[offset] = val;
+    }
+
+    fn size(&self) -> Option<usize> {
+        Some(self.pixels.len())
+    }
+}
+
+impl std::fmt::Display for Framebuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut image = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                image += if self.pixel(x, y) == 0 { " " } else { "#" };
+            }
+            if y < self.height - 1 {
+                image += "\n";
+            }
+        }
+        write!(f, "{}", image)
+    }
+}
+
+// Writes append a character to the output, reads consume typed characters or give -1 when
+// there are none left.
+#[derive(Default)]
+pub struct Console {
+    pub output: String,
+    input: VecDeque<u8>,
+}
+
+impl Console {
+    pub fn type_text(&mut self, text: &str) {
+        self.input.extend(text.bytes());
+    }
+}
+
+impl Device for Console {
+    fn read(&mut self, _offset: usize) -> i64 {
+        self.input.pop_front().map_or(-1, i64::from)
+    }
+
+    fn write(&mut self, _offset: usize, val: i64) {
+        self.output.push(val as u8 as char);
+    }
+}
+
+#[cfg(test)]
+use super::Intcode;
+#[cfg(test)]
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn cannot_map_overlapping_devices() {
+    let mut ic = Intcode::new(vec![99], true);
+    ic.map_device(100..110, Rc::new(RefCell::new(Clock::default())))
+        .unwrap();
+    assert!(ic
+        .map_device(105..106, Rc::new(RefCell::new(Clock::default())))
+        .is_err());
+    assert!(ic
+        .map_device(110..110, Rc::new(RefCell::new(Clock::default())))
+        .is_err());
+    assert!(ic
+        .map_device(110..111, Rc::new(RefCell::new(Clock::default())))
+        .is_ok());
+    assert_eq!(
+        ic.map_device(200..205, Rc::new(RefCell::new(Framebuffer::new(2, 2)))),
+        Err("Cannot map a device with 4 cells to 200..205".to_string())
+    );
+}
+
+#[test]
+fn can_read_the_clock_and_rng() {
+    // out [1000], out [1000], out [1001], with a clock that only counts finished instructions
+    let memory = vec![4, 1000, 4, 1000, 4, 1001, 99];
+    let mut ic = Intcode::new(memory, true);
+    ic.map_device(1000..1001, Rc::new(RefCell::new(Clock::default())))
+        .unwrap();
+    ic.map_device(1001..1002, Rc::new(RefCell::new(Rng::new(7))))
+        .unwrap();
+    ic.progress_program().unwrap();
+    assert_eq!(ic.dequeue_output(), Some(0));
+    assert_eq!(ic.dequeue_output(), Some(1));
+    let random = ic.dequeue_output().unwrap();
+    assert!(random >= 0);
+    assert_eq!(random, Rng::new(7).read(0));
+    assert_ne!(random, Rng::new(8).read(0));
+
+    // Waiting for input isn't an instruction: in [7], out [1000]
+    let mut ic = Intcode::new(vec![3, 7, 4, 1000, 99, 0, 0, 0], true);
+    ic.map_device(1000..1001, Rc::new(RefCell::new(Clock::default())))
+        .unwrap();
+    ic.progress_program().unwrap();
+    ic.queue_input(5);
+    ic.progress_program().unwrap();
+    assert_eq!(ic.dequeue_output(), Some(1));
+}
+
+#[test]
+fn can_draw_to_a_framebuffer_and_console() {
+    // A 2x2 checkerboard, "hi" on the console, then echo back whatever was typed
+    let memory = vec![
+        1101, 0, 1, 2000, 1101, 0, 1, 2003, 1101, 0, 104, 3000, 1101, 0, 105, 3000, 1001, 3000, 0,
+        3000, 99,
+    ];
+    let fb = Rc::new(RefCell::new(Framebuffer::new(2, 2)));
+    let console = Rc::new(RefCell::new(Console::default()));
+    console.borrow_mut().type_text("!");
+    let mut ic = Intcode::new(memory, true);
+    ic.map_device(2000..2004, fb.clone()).unwrap();
+    ic.map_device(3000..3001, console.clone()).unwrap();
+    ic.progress_program().unwrap();
+    assert_eq!(fb.borrow().to_string(), "# \n #");
+    assert_eq!(console.borrow().output, "hi!");
+}
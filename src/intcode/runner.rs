@@ -0,0 +1,369 @@
+use super::assembler::assemble;
+use super::linter::{lint, to_json, DAY_02, DAY_05, DAY_09};
+use super::loader::load_program;
+use super::stdlib::link_with_stdlib;
+use super::validator::validate;
+use super::{DebugServer, Intcode, IntcodeState};
+use std::fs;
+use std::io::{BufRead, Write};
+use std::net::TcpListener;
+
+pub const USAGE: &str = "\
+usage: intcode <program> [options]
+
+Programs ending in .asm are assembled and linked with the standard library first.
+
+  --input <values>     queue input, comma separated numbers (or text with --ascii)
+  --input-file <path>  queue input read from a file
+  --stdin              queue input read from stdin
+  --repl               prompt for input whenever the program asks for more
+  --ascii              send input as ASCII text and print outputs below 128 as characters
+  --no-pad             don't pad memory out to the default size
+  --detect-loops       stop the program if it gets stuck in an infinite loop
+  --strict             check the program's parameter modes before and while running it
+  --lint <format>      check the program for likely bugs instead of running it, printing
+                       warnings as text or json
+  --target <machine>   the instruction set --lint checks against: day02, day05 or day09
+                       (the default)
+  --debug <addr>       serve the debugger protocol on addr (e.g. 127.0.0.1:4000) instead of running";
+
+// How often --detect-loops samples the machine, in instructions
+const LOOP_CHECK_INTERVAL: u64 = 1000;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    pub program: String,
+    pub inputs: Vec<String>,
+    pub input_files: Vec<String>,
+    pub stdin: bool,
+    pub repl: bool,
+    pub ascii: bool,
+    pub no_pad: bool,
+    pub detect_loops: bool,
+    pub strict: bool,
+    pub debug: Option<String>,
+    pub lint: Option<String>,
+    pub target: Option<&'static [i64]>,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", arg))
+        };
+        match arg.as_str() {
+            "--input" => options.inputs.push(value()?),
+            "--input-file" => options.input_files.push(value()?),
+            "--stdin" => options.stdin = true,
+            "--repl" => options.repl = true,
+            "--ascii" => options.ascii = true,
+            "--no-pad" => options.no_pad = true,
+            "--detect-loops" => options.detect_loops = true,
+            "--strict" => options.strict = true,
+            "--debug" => options.debug = Some(value()?),
+            "--lint" => match value()?.as_str() {
+                format @ "text" | format @ "json" => options.lint = Some(format.to_string()),
+                format => return Err(format!("Unknown lint format {}", format)),
+            },
+            "--target" => {
+                options.target = Some(match value()?.as_str() {
+                    "day02" => DAY_02,
+                    "day05" => DAY_05,
+                    "day09" => DAY_09,
+                    target => return Err(format!("Unknown target {}", target)),
+                })
+            }
+            _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+            _ if options.program.is_empty() => options.program = arg.clone(),
+            _ => return Err(format!("Unexpected argument {}", arg)),
+        }
+    }
+    if options.program.is_empty() {
+        return Err("Missing program file".to_string());
+    }
+    if options.repl && options.debug.is_some() {
+        return Err("--repl can't be used with --debug".to_string());
+    }
+    if options.target.is_some() && options.lint.is_none() {
+        return Err("--target only applies to --lint".to_string());
+    }
+    if options.stdin && options.repl {
+        return Err("--stdin and --repl both want to read stdin".to_string());
+    }
+    Ok(options)
+}
+
+fn queue_text(ic: &mut Intcode, text: &str, ascii: bool) -> Result<(), String> {
+    if ascii {
+        for b in text.bytes() {
+            ic.queue_input(i64::from(b));
+        }
+    } else {
+        // Values can be separated by commas, whitespace or both
+        for value in text.split(|c: char| c == ',' || c.is_whitespace()) {
+            if !value.is_empty() {
+                let n = value
+                    .parse()
+                    .map_err(|_| format!("Invalid input value {}", value))?;
+                ic.queue_input(n);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn flush_outputs<W: Write>(ic: &mut Intcode, ascii: bool, out: &mut W) -> Result<(), String> {
+    let mut text = String::new();
+    while let Some(o) = ic.dequeue_output() {
+        if ascii && (0..128).contains(&o) {
+            text.push(o as u8 as char);
+        } else {
+            if !text.is_empty() && !text.ends_with('\n') {
+                text.push('\n');
+            }
+            text += &format!("{}\n", o);
+        }
+    }
+    out.write_all(text.as_bytes())
+        .and_then(|_| out.flush())
+        .map_err(|e| e.to_string())
+}
+
+fn serve_debugger<E: Write>(ic: &mut Intcode, addr: &str, report: &mut E) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Could not listen on {}: {}", addr, e))?;
+    writeln!(report, "debugger listening on {}", addr).map_err(|e| e.to_string())?;
+    let mut server = DebugServer::new(ic.clone());
+    server.serve(&listener)?;
+    *ic = server.into_inner();
+    Ok(())
+}
+
+fn run_repl<R: BufRead, W: Write, E: Write>(
+    ic: &mut Intcode,
+    options: &Options,
+    input: &mut R,
+    out: &mut W,
+    report: &mut E,
+) -> Result<(), String> {
+    loop {
+        ic.progress_program()?;
+        flush_outputs(ic, options.ascii, out)?;
+        if !options.repl || ic.get_state() != IntcodeState::PollingInput {
+            return Ok(());
+        }
+
+        write!(out, "> ")
+            .and_then(|_| out.flush())
+            .map_err(|e| e.to_string())?;
+        let mut line = String::new();
+        match input.read_line(&mut line) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {
+                let line = if options.ascii {
+                    line
+                } else {
+                    line.trim().to_string()
+                };
+                if let Err(e) = queue_text(ic, &line, options.ascii) {
+                    writeln!(report, "{}", e).map_err(|e| e.to_string())?;
+                }
+            }
+            Err(e) => return Err(format!("Could not read stdin: {}", e)),
+        }
+    }
+}
+
+pub fn run<R: BufRead, W: Write, E: Write>(
+    options: &Options,
+    input: &mut R,
+    out: &mut W,
+    report: &mut E,
+) -> Result<(), String> {
+    let memory = if options.program.ends_with(".asm") {
+        let source = fs::read_to_string(&options.program)
+            .map_err(|e| format!("Could not read file {}: {}", options.program, e))?;
+        link_with_stdlib(&[assemble(&options.program, &source)?])?
+    } else {
+        load_program(&options.program)?
+    };
+    if let Some(format) = &options.lint {
+        let warnings = lint(&memory, options.target.unwrap_or(DAY_09));
+        let text = if format == "json" {
+            to_json(&warnings)
+        } else {
+            warnings.iter().map(|w| format!("{}\n", w)).collect()
+        };
+        return out.write_all(text.as_bytes()).map_err(|e| e.to_string());
+    }
+    if options.strict {
+        let issues = validate(&memory);
+        for issue in &issues {
+            writeln!(report, "{}: {}", issue.addr, issue.message).map_err(|e| e.to_string())?;
+        }
+        if !issues.is_empty() {
+            return Err(format!("{} invalid instructions", issues.len()));
+        }
+    }
+    let mut ic = Intcode::new(memory, !options.no_pad);
+    ic.enable_call_stack();
+    ic.set_strict(options.strict);
+    // The state gets printed below, once the outputs have been flushed
+    ic.set_dump_on_error(false);
+    if options.detect_loops {
+        ic.enable_loop_detection(LOOP_CHECK_INTERVAL);
+    }
+
+    for text in &options.inputs {
+        let text = if options.ascii {
+            format!("{}\n", text)
+        } else {
+            text.clone()
+        };
+        queue_text(&mut ic, &text, options.ascii)?;
+    }
+    for filename in &options.input_files {
+        let text = fs::read_to_string(filename)
+            .map_err(|e| format!("Could not read file {}: {}", filename, e))?;
+        queue_text(&mut ic, &text, options.ascii)?;
+    }
+    if options.stdin {
+        let mut text = String::new();
+        input
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Could not read stdin: {}", e))?;
+        queue_text(&mut ic, &text, options.ascii)?;
+    }
+
+    let result = match &options.debug {
+        Some(addr) => serve_debugger(&mut ic, addr, report),
+        None => run_repl(&mut ic, options, input, out, report),
+    };
+    flush_outputs(&mut ic, options.ascii, out)?;
+
+    let state = match ic.get_state() {
+        IntcodeState::Done => "halted".to_string(),
+        IntcodeState::PollingInput => format!("waiting for input at {}", ic.ip()),
+        IntcodeState::Looping { start, period } => {
+            format!("stuck in a loop at {} (period {})", start, period)
+        }
+        _ if result.is_err() => format!("faulted at {}", ic.ip()),
+        _ => format!("running at {}", ic.ip()),
+    };
+    writeln!(
+        report,
+        "state: {}, instructions executed: {}",
+        state,
+        ic.cycles()
+    )
+    .map_err(|e| e.to_string())?;
+    if result.is_err() {
+        writeln!(report, "{}", ic).map_err(|e| e.to_string())?;
+    }
+    result
+}
+
+#[cfg(test)]
+fn run_with(args: &[&str], stdin: &str) -> (Result<(), String>, String, String) {
+    let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+    let options = parse_args(&args).unwrap();
+    let (mut out, mut report) = (Vec::new(), Vec::new());
+    let result = run(&options, &mut stdin.as_bytes(), &mut out, &mut report);
+    (
+        result,
+        String::from_utf8(out).unwrap(),
+        String::from_utf8(report).unwrap(),
+    )
+}
+
+#[test]
+fn can_parse_runner_arguments() {
+    let args: Vec<String> = ["prog.txt", "--input", "1,2", "--ascii", "--repl"]
+        .iter()
+        .map(|a| a.to_string())
+        .collect();
+    assert_eq!(
+        parse_args(&args),
+        Ok(Options {
+            program: "prog.txt".to_string(),
+            inputs: vec!["1,2".to_string()],
+            ascii: true,
+            repl: true,
+            ..Options::default()
+        })
+    );
+    assert_eq!(
+        parse_args(&["a".to_string(), "b".to_string()]),
+        Err("Unexpected argument b".to_string())
+    );
+    assert_eq!(
+        parse_args(&["x".to_string(), "--input".to_string()]),
+        Err("Missing value for --input".to_string())
+    );
+    assert_eq!(
+        parse_args(&["x".to_string(), "--target".to_string(), "day05".to_string()]),
+        Err("--target only applies to --lint".to_string())
+    );
+}
+
+#[test]
+fn can_run_programs_from_files() {
+    let (result, out, report) = run_with(&["res/day_05.txt", "--input", "5"], "");
+    assert_eq!(result, Ok(()));
+    assert_eq!(out, "584126\n");
+    assert!(report.starts_with("state: halted, instructions executed: "));
+
+    let (result, out, report) = run_with(&["res/day_09.txt", "--stdin"], "1\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(out, "2662308295\n");
+    assert!(report.starts_with("state: halted"));
+
+    // Values split over lines and ending in a newline, from both a file and stdin
+    let filename = std::env::temp_dir().join("intcode_runner_inputs.txt");
+    let filename = filename.to_str().unwrap();
+    fs::write(filename, "3\n4\n").unwrap();
+    let adder = std::env::temp_dir().join("intcode_runner_adder.txt");
+    let adder = adder.to_str().unwrap();
+    // Outputs the sum of four inputs, kept in [30] and [31]
+    fs::write(
+        adder,
+        "3,30,3,31,1,30,31,30,3,31,1,30,31,30,3,31,1,30,31,30,4,30,99\n",
+    )
+    .unwrap();
+    let args = [adder, "--input-file", filename, "--stdin"];
+    let (result, out, _) = run_with(&args, "1 2\n");
+    fs::remove_file(filename).unwrap();
+    fs::remove_file(adder).unwrap();
+    assert_eq!(result, Ok(()));
+    assert_eq!(out, "10\n");
+
+    let lint = ["res/day_09.txt", "--lint", "text", "--target", "day05"];
+    let (result, out, _) = run_with(&lint, "");
+    assert_eq!(result, Ok(()));
+    assert!(out.starts_with("15: arb (9) isn't supported by the target [unsupported-opcode]\n"));
+}
+
+#[test]
+fn can_prompt_for_input_in_the_repl() {
+    let filename = std::env::temp_dir().join("intcode_runner_echo.txt");
+    let filename = filename.to_str().unwrap();
+    // Echo every input until a zero comes in
+    fs::write(filename, "3,9,4,9,1005,9,0,99,0,0\n").unwrap();
+
+    let (result, out, report) = run_with(&[filename, "--repl", "--no-pad"], "7\n8 9\n0\n");
+    assert_eq!(result, Ok(()));
+    assert_eq!(out, "> 7\n> 8\n9\n> 0\n");
+    assert_eq!(report, "state: halted, instructions executed: 13\n");
+
+    let (_, out, report) = run_with(&[filename, "--repl", "--ascii", "--no-pad"], "hi\n");
+    fs::remove_file(filename).unwrap();
+    assert_eq!(out, "> hi\n> ");
+    assert_eq!(
+        report,
+        "state: waiting for input at 0, instructions executed: 9\n"
+    );
+}
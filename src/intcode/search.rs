@@ -0,0 +1,171 @@
+use super::{Intcode, IntcodeState};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+// Runs a fresh machine per candidate across every core. setup patches memory or queues
+// input on the machine before it runs, and predicate decides whether the finished machine
+// is a match. Candidates whose program fails to run, or that are still running after
+// max_cycles instructions, are never matches. setup can also enable loop detection so
+// candidates that get stuck stop early in IntcodeState::Looping.
+pub fn find_first<C, S, P>(
+    program: &[i64],
+    pad_memory: bool,
+    max_cycles: u64,
+    candidates: &[C],
+    setup: S,
+    predicate: P,
+) -> Option<C>
+where
+    C: Clone + Sync,
+    S: Fn(&C, &mut Intcode) + Sync,
+    P: Fn(&C, &mut Intcode) -> bool + Sync,
+{
+    search(
+        program, pad_memory, max_cycles, candidates, setup, predicate, true,
+    )
+    .first()
+    .map(|&i| candidates[i].clone())
+}
+
+pub fn find_all<C, S, P>(
+    program: &[i64],
+    pad_memory: bool,
+    max_cycles: u64,
+    candidates: &[C],
+    setup: S,
+    predicate: P,
+) -> Vec<C>
+where
+    C: Clone + Sync,
+    S: Fn(&C, &mut Intcode) + Sync,
+    P: Fn(&C, &mut Intcode) -> bool + Sync,
+{
+    search(
+        program, pad_memory, max_cycles, candidates, setup, predicate, false,
+    )
+    .into_iter()
+    .map(|i| candidates[i].clone())
+    .collect()
+}
+
+fn workers() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Runs the machine until it stops on its own, and says whether it did so without failing
+// inside the budget
+fn run(ic: &mut Intcode, max_cycles: u64) -> bool {
+    while ic.cycles() < max_cycles {
+        match ic.step() {
+            Ok(IntcodeState::Running) => {}
+            Ok(_) => return true,
+            Err(_) => return false,
+        }
+    }
+    false
+}
+
+// Returns the indices of matching candidates in order. When stopping early workers keep
+// going until they pass the earliest match found so far, so the first match is always
+// the same one a sequential search would find.
+fn search<C, S, P>(
+    program: &[i64],
+    pad_memory: bool,
+    max_cycles: u64,
+    candidates: &[C],
+    setup: S,
+    predicate: P,
+    first_only: bool,
+) -> Vec<usize>
+where
+    C: Sync,
+    S: Fn(&C, &mut Intcode) + Sync,
+    P: Fn(&C, &mut Intcode) -> bool + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let earliest = AtomicUsize::new(usize::MAX);
+    let matches = Mutex::new(Vec::new());
+
+    thread::scope(|scope| {
+        for _ in 0..workers().min(candidates.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= candidates.len() || (first_only && i > earliest.load(Ordering::Relaxed)) {
+                    break;
+                }
+                let candidate = &candidates[i];
+                let mut ic = Intcode::new(program.to_vec(), pad_memory);
+                setup(candidate, &mut ic);
+                if run(&mut ic, max_cycles) && predicate(candidate, &mut ic) {
+                    earliest.fetch_min(i, Ordering::Relaxed);
+                    matches.lock().unwrap().push(i);
+                }
+            });
+        }
+    });
+
+    let mut matches = matches.into_inner().unwrap();
+    matches.sort_unstable();
+    matches
+}
+
+#[cfg(test)]
+fn doubler() -> Vec<i64> {
+    // out 2 * in
+    vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]
+}
+
+#[test]
+fn can_find_the_first_matching_input() {
+    let candidates: Vec<i64> = (0..1000).collect();
+    let setup = |n: &i64, ic: &mut Intcode| ic.queue_input(*n);
+    let found = find_first(&doubler(), false, 100, &candidates, setup, |_, ic| {
+        ic.dequeue_output().is_some_and(|o| o % 7 == 6)
+    });
+    assert_eq!(found, Some(3));
+
+    let found = find_first(&doubler(), false, 100, &candidates, setup, |_, ic| {
+        ic.dequeue_output() == Some(-1)
+    });
+    assert_eq!(found, None);
+}
+
+#[test]
+fn can_find_every_match_by_patching_memory() {
+    // mem[0] = a * b, with a and b patched in as immediates
+    let program = vec![1102, 0, 0, 0, 99];
+    let candidates: Vec<(i64, i64)> = (1..=12)
+        .flat_map(|a| (1..=12).map(move |b| (a, b)))
+        .collect();
+    let found = find_all(
+        &program,
+        false,
+        100,
+        &candidates,
+        |&(a, b), ic| ic.write_range(1, &[a, b]).unwrap(),
+        |_, ic| ic.read(0) == Ok(36),
+    );
+    let expected: Vec<(i64, i64)> = candidates
+        .iter()
+        .copied()
+        .filter(|(a, b)| a * b == 36)
+        .collect();
+    assert_eq!(found, expected);
+}
+
+#[test]
+fn candidates_that_run_past_the_budget_never_match() {
+    // Loops forever unless the input is zero: in [7]; jt [7], 2; hlt
+    let program = vec![3, 7, 1005, 7, 2, 99, 0, 0];
+    let candidates: Vec<i64> = vec![1, 2, 0];
+    let found = find_all(
+        &program,
+        false,
+        1000,
+        &candidates,
+        |n, ic| ic.queue_input(*n),
+        |_, ic| ic.get_state() == IntcodeState::Done,
+    );
+    assert_eq!(found, vec![0]);
+}
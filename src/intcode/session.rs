@@ -0,0 +1,365 @@
+use super::{Intcode, IntcodeState, Observer};
+use std::cell::{Ref, RefCell};
+use std::fmt;
+use std::fs;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IoKind {
+    Input,
+    Output,
+    // Every machine's events end with one of these, which have no value
+    Halt,
+    Stop,
+}
+
+// cycle is the number of the instruction (counting from 1) that consumed or produced value,
+// or for the last event the number of instructions the machine had run when it halted or
+// was added to the session while it was still going
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IoEvent {
+    pub cycle: u64,
+    pub kind: IoKind,
+    pub value: i64,
+}
+
+impl IoEvent {
+    pub fn input(cycle: u64, value: i64) -> Self {
+        IoEvent {
+            cycle,
+            kind: IoKind::Input,
+            value,
+        }
+    }
+
+    pub fn output(cycle: u64, value: i64) -> Self {
+        IoEvent {
+            cycle,
+            kind: IoKind::Output,
+            value,
+        }
+    }
+
+    pub fn end(cycle: u64, halted: bool) -> Self {
+        IoEvent {
+            cycle,
+            kind: if halted { IoKind::Halt } else { IoKind::Stop },
+            value: 0,
+        }
+    }
+
+    fn is_end(&self) -> bool {
+        self.kind == IoKind::Halt || self.kind == IoKind::Stop
+    }
+}
+
+impl fmt::Display for IoEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            IoKind::Input => write!(f, "{} in {}", self.cycle, self.value),
+            IoKind::Output => write!(f, "{} out {}", self.cycle, self.value),
+            IoKind::Halt => write!(f, "{} halt", self.cycle),
+            IoKind::Stop => write!(f, "{} stop", self.cycle),
+        }
+    }
+}
+
+// A machine's I/O as it happens, kept by the machine itself once recording is enabled
+#[derive(Clone, Debug, Default)]
+pub(super) struct Recording {
+    // The instruction that's executing
+    cycle: u64,
+    events: Vec<IoEvent>,
+}
+
+impl Observer for Recording {
+    fn before_instruction(&mut self, ic: &Intcode, _ip: usize) {
+        self.cycle = ic.cycles();
+    }
+
+    fn input_consumed(&mut self, value: i64) {
+        self.events.push(IoEvent::input(self.cycle, value));
+    }
+
+    fn output_produced(&mut self, value: i64) {
+        self.events.push(IoEvent::output(self.cycle, value));
+    }
+}
+
+impl Intcode {
+    pub fn enable_recording(&mut self) {
+        self.recording = Some(RefCell::new(Recording::default()));
+    }
+
+    pub fn recording(&self) -> Option<Ref<'_, [IoEvent]>> {
+        let recording = self.recording.as_ref()?.borrow();
+        Some(Ref::map(recording, |recording| recording.events.as_slice()))
+    }
+}
+
+// The I/O of one or more named machines. Session files have one event per line in the
+// form `<machine> <cycle> in|out <value>` or `<machine> <cycle> halt|stop`, and # starts a
+// comment.
+#[derive(Debug, Default, PartialEq)]
+pub struct Session {
+    machines: Vec<(String, Vec<IoEvent>)>,
+}
+
+impl Session {
+    pub fn add(&mut self, name: &str, ic: &Intcode) -> Result<(), String> {
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(format!("Invalid machine name: {:?}", name));
+        }
+        if self.events(name).is_some() {
+            return Err(format!("Machine {} is already in the session", name));
+        }
+        let mut events = ic
+            .recording()
+            .ok_or_else(|| format!("Machine {} was not recording", name))?
+            .to_vec();
+        events.push(IoEvent::end(
+            ic.cycles(),
+            ic.get_state() == IntcodeState::Done,
+        ));
+        self.machines.push((name.to_string(), events));
+        Ok(())
+    }
+
+    pub fn events(&self, name: &str) -> Option<&[IoEvent]> {
+        self.machines
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, events)| events.as_slice())
+    }
+
+    pub fn parse(input: &str) -> Result<Session, String> {
+        let mut session = Session::default();
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| format!("Line {}: {}: {}", i + 1, message, line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let cycle = match fields.get(1) {
+                Some(cycle) => cycle.parse().map_err(|_| error("invalid cycle"))?,
+                None => return Err(error("expected <machine> <cycle> <event>")),
+            };
+            let value = || match fields[3..] {
+                [value] => value.parse().map_err(|_| error("invalid value")),
+                _ => Err(error("expected one value")),
+            };
+            let event = match (fields.get(2), fields.len()) {
+                (Some(&"in"), _) => IoEvent::input(cycle, value()?),
+                (Some(&"out"), _) => IoEvent::output(cycle, value()?),
+                (Some(&"halt"), 3) => IoEvent::end(cycle, true),
+                (Some(&"stop"), 3) => IoEvent::end(cycle, false),
+                _ => return Err(error("expected in, out, halt or stop")),
+            };
+            let events = session.events(fields[0]).unwrap_or_default();
+            if events.last().is_some_and(IoEvent::is_end) {
+                return Err(error("event after the machine's end"));
+            }
+            match session.machines.iter_mut().find(|(n, _)| n == fields[0]) {
+                Some((_, events)) => events.push(event),
+                None => session.machines.push((fields[0].to_string(), vec![event])),
+            }
+        }
+        for (name, events) in &session.machines {
+            if !events.last().is_some_and(IoEvent::is_end) {
+                return Err(format!("Machine {} has no halt or stop at the end", name));
+            }
+        }
+        Ok(session)
+    }
+
+    pub fn load(filename: &str) -> Result<Session, String> {
+        let input = fs::read_to_string(filename)
+            .map_err(|e| format!("Could not read file {}: {}", filename, e))?;
+        Session::parse(&input)
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), String> {
+        fs::write(filename, self.to_string())
+            .map_err(|e| format!("Could not write file {}: {}", filename, e))
+    }
+
+    // Runs a fresh machine feeding it the recorded inputs exactly when it asks for them, and
+    // fails on the first event that doesn't match the recording. The replay ends where the
+    // recording did, so it fails if the machine halts at a different cycle, or is still
+    // going after the cycle it halted or stopped at.
+    pub fn replay(&self, name: &str, ic: &mut Intcode) -> Result<(), String> {
+        let events = self
+            .events(name)
+            .ok_or_else(|| format!("No machine named {} in the session", name))?;
+        let end = events[events.len() - 1];
+        let mut next = 0;
+        let diverged = |next: usize, actual: String| {
+            Err(format!(
+                "Replay of {} diverged at event {}: expected {} but got {}",
+                name, next, events[next], actual
+            ))
+        };
+
+        loop {
+            let halted = ic.get_state() == IntcodeState::Done;
+            if next == events.len() - 1
+                && ic.cycles() == end.cycle
+                && halted == (end.kind == IoKind::Halt)
+            {
+                return Ok(());
+            }
+            if ic.cycles() >= end.cycle {
+                let actual = format!("an instruction at cycle {}", ic.cycles() + 1);
+                return diverged(next, actual);
+            }
+
+            let state = ic.step()?;
+            while let Some(o) = ic.dequeue_output() {
+                let actual = IoEvent::output(ic.cycles(), o);
+                if events[next] != actual {
+                    return diverged(next, actual.to_string());
+                }
+                next += 1;
+            }
+            match state {
+                IntcodeState::PollingInput => match events[next] {
+                    event if event.kind == IoKind::Input && event.cycle == ic.cycles() + 1 => {
+                        ic.queue_input(event.value);
+                        next += 1;
+                    }
+                    _ => {
+                        let actual = format!("a request for input at cycle {}", ic.cycles() + 1);
+                        return diverged(next, actual);
+                    }
+                },
+                IntcodeState::Done if events[next] != IoEvent::end(ic.cycles(), true) => {
+                    return diverged(next, format!("a halt at cycle {}", ic.cycles()));
+                }
+                IntcodeState::Looping { start, .. } => {
+                    return diverged(next, format!("a loop at {}", start));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# intcode session")?;
+        for (name, events) in &self.machines {
+            for event in events {
+                writeln!(f, "{} {}", name, event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+fn feedback_program() -> Vec<i64> {
+    vec![
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ]
+}
+
+#[test]
+fn can_record_and_replay_a_network_of_machines() {
+    let mut machines: Vec<Intcode> = [9, 8, 7, 6, 5]
+        .iter()
+        .map(|phase| {
+            let mut ic = Intcode::new(feedback_program(), false);
+            ic.enable_recording();
+            ic.queue_input(*phase);
+            ic
+        })
+        .collect();
+    assert_eq!(super::run_ring(&mut machines, 0), Ok(vec![139_629_729]));
+
+    let mut session = Session::default();
+    for (i, ic) in machines.iter().enumerate() {
+        session.add(&format!("amp{}", i), ic).unwrap();
+    }
+    assert_eq!(
+        session.events("amp0").unwrap()[..2],
+        [IoEvent::input(1, 9), IoEvent::input(3, 0)]
+    );
+
+    let filename = std::env::temp_dir().join("intcode_session_test.txt");
+    let filename = filename.to_str().unwrap();
+    session.save(filename).unwrap();
+    let loaded = Session::load(filename).unwrap();
+    fs::remove_file(filename).unwrap();
+    assert_eq!(loaded, session);
+
+    for i in 0..5 {
+        let mut ic = Intcode::new(feedback_program(), false);
+        loaded.replay(&format!("amp{}", i), &mut ic).unwrap();
+    }
+}
+
+#[test]
+fn replay_reports_the_first_divergence() {
+    let session = Session::parse("# doubler\ndbl 1 in 21\ndbl 3 out 43\ndbl 4 halt\n").unwrap();
+    let doubler = vec![3, 9, 1002, 9, 2, 9, 4, 9, 99, 0];
+    assert_eq!(
+        session.replay("dbl", &mut Intcode::new(doubler.clone(), false)),
+        Err("Replay of dbl diverged at event 1: expected 3 out 43 but got 3 out 42".to_string())
+    );
+
+    let session = Session::parse("dbl 2 in 21\ndbl 4 halt\n").unwrap();
+    assert_eq!(
+        session.replay("dbl", &mut Intcode::new(doubler.clone(), false)),
+        Err("Replay of dbl diverged at event 0: expected 2 in 21 but got a request for input at cycle 1".to_string())
+    );
+
+    let session = Session::parse("dbl 1 in 21\ndbl 3 out 42\ndbl 6 halt\n").unwrap();
+    assert_eq!(
+        session.replay("dbl", &mut Intcode::new(doubler.clone(), false)),
+        Err(
+            "Replay of dbl diverged at event 2: expected 6 halt but got a halt at cycle 4"
+                .to_string()
+        )
+    );
+
+    // Recorded before the machine got as far as halting
+    let session = Session::parse("dbl 1 in 21\ndbl 3 out 42\ndbl 3 stop\n").unwrap();
+    assert_eq!(
+        session.replay("dbl", &mut Intcode::new(doubler, false)),
+        Ok(())
+    );
+
+    // out 1; jt 1, 0 never halts, so the replay ends where the recording stopped
+    let program = vec![104, 1, 1105, 1, 0];
+    let replay = |session: &str| {
+        Session::parse(session)
+            .unwrap()
+            .replay("forever", &mut Intcode::new(program.clone(), false))
+    };
+    assert_eq!(
+        replay("forever 1 out 1\nforever 3 out 1\nforever 4 stop\n"),
+        Ok(())
+    );
+    assert_eq!(
+        replay("forever 1 out 1\nforever 4 stop\n"),
+        Err("Replay of forever diverged at event 1: expected 4 stop but got 3 out 1".to_string())
+    );
+    assert_eq!(
+        replay("forever 1 out 1\nforever 3 out 1\nforever 4 halt\n"),
+        Err("Replay of forever diverged at event 2: expected 4 halt but got an instruction at cycle 5".to_string())
+    );
+
+    assert_eq!(
+        Session::parse("dbl 1 sideways 21"),
+        Err("Line 1: expected in, out, halt or stop: dbl 1 sideways 21".to_string())
+    );
+    assert_eq!(
+        Session::parse("dbl 1 in 21"),
+        Err("Machine dbl has no halt or stop at the end".to_string())
+    );
+    assert_eq!(
+        Session::parse("dbl 4 halt\ndbl 5 out 1"),
+        Err("Line 2: event after the machine's end: dbl 5 out 1".to_string())
+    );
+}
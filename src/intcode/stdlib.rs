@@ -0,0 +1,177 @@
+use super::assembler::assemble;
+use super::linker::{link, Object};
+
+// Routines for assembled programs, all following the same relative base convention (the
+// one CallStack recognises). rb points at the top of the caller's stack, and a call looks
+// like
+//
+//             add back, 0, [rb+0]     # the return address
+//             add 42, 0, [rb+1]       # arguments from [rb+1] up
+//             jt 1, print_int
+//     back:   ...                     # the result, if any, is in [rb+1]
+//
+// Routines can use any cell from rb up as scratch, and leave rb where they found it. A
+// program sets up its stack with `arb stack` before making any calls. Memory is padded, so
+// the stack has the room between the end of the program and the heap.
+
+const SOURCES: &[(&str, &str)] = &[
+    ("print_int", include_str!("stdlib/print_int.asm")),
+    ("read_int", include_str!("stdlib/read_int.asm")),
+    ("memcpy", include_str!("stdlib/memcpy.asm")),
+    ("mac", include_str!("stdlib/mac.asm")),
+    ("alloc", include_str!("stdlib/alloc.asm")),
+    ("stack", include_str!("stdlib/stack.asm")),
+];
+
+pub fn modules() -> Result<Vec<Object>, String> {
+    SOURCES
+        .iter()
+        .map(|(name, source)| assemble(name, source))
+        .collect()
+}
+
+// Links the objects (the first one holding the entry point) followed by the whole library
+pub fn link_with_stdlib(objects: &[Object]) -> Result<Vec<i64>, String> {
+    let mut objects = objects.to_vec();
+    objects.extend(modules()?);
+    link(&objects)
+}
+
+#[cfg(test)]
+use super::Intcode;
+
+// Runs main linked with the library, returning its outputs
+#[cfg(test)]
+fn run(main: &str, input: &str) -> Vec<i64> {
+    let main = assemble("main", main).unwrap();
+    let program = link_with_stdlib(&[main]).unwrap();
+    let input = input.bytes().map(i64::from).collect::<Vec<_>>();
+    Intcode::run_iter(program, input)
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+// Calls one routine with the given arguments, and outputs its result after anything it output
+#[cfg(test)]
+fn call(routine: &str, args: &[i64], input: &str) -> Vec<i64> {
+    let args: Vec<String> = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| format!("add {}, 0, [rb+{}]", arg, i + 1))
+        .collect();
+    run(
+        &format!(
+            ".import stack, {0}\narb stack\nadd back, 0, [rb+0]\n{1}\njt 1, {0}\nback: out [rb+1]\nhlt",
+            routine,
+            args.join("\n")
+        ),
+        input,
+    )
+}
+
+#[cfg(test)]
+fn text(outputs: &[i64]) -> String {
+    outputs.iter().map(|&c| c as u8 as char).collect()
+}
+
+#[test]
+fn can_print_and_read_numbers() {
+    for &n in &[0, 7, 10, -1234, 1_000_000_007, i64::MAX, i64::MIN] {
+        let outputs = call("print_int", &[n], "");
+        assert_eq!(text(&outputs[..outputs.len() - 1]), n.to_string());
+    }
+
+    assert_eq!(call("read_int", &[], "1234\n"), vec![1234]);
+    assert_eq!(call("read_int", &[], "x: -56,7"), vec![-56]);
+    assert_eq!(call("read_int", &[], "- 3 "), vec![3]);
+}
+
+#[test]
+fn can_copy_and_multiply_arrays() {
+    let outputs = run(
+        "
+        .import stack, memcpy, mac
+                arb stack
+                add copied, 0, [rb+0]
+                add b, 0, [rb+1]
+                add a, 0, [rb+2]
+                add 3, 0, [rb+3]
+                jt 1, memcpy
+        copied: out [b+2]
+                add summed, 0, [rb+0]
+                add a, 0, [rb+1]
+                add b, 0, [rb+2]
+                add 3, 0, [rb+3]
+                jt 1, mac
+        summed: out [rb+1]
+                hlt
+        a:      .data 2, 3, 4
+        b:      .data 0, 0, 0
+        ",
+        "",
+    );
+    assert_eq!(outputs, vec![4, 2 * 2 + 3 * 3 + 4 * 4]);
+}
+
+#[test]
+fn can_allocate_from_the_heap() {
+    let outputs = run(
+        "
+        .import stack, alloc
+                arb stack
+                add first, 0, [rb+0]
+                add 5, 0, [rb+1]
+                jt 1, alloc
+        first:  out [rb+1]
+                add second, 0, [rb+0]
+                add 2, 0, [rb+1]
+                jt 1, alloc
+        second: out [rb+1]
+                hlt
+        ",
+        "",
+    );
+    assert_eq!(outputs, vec![4091, 4089]);
+
+    // Asking for more than is left between the program and the top of memory
+    let main = assemble(
+        "main",
+        "
+        .import stack, alloc
+                arb stack
+                add back, 0, [rb+0]
+                add 4096, 0, [rb+1]
+                jt 1, alloc
+        back:   hlt
+        ",
+    )
+    .unwrap();
+    let program = link_with_stdlib(&[main]).unwrap();
+    let mut ic = Intcode::new(program, true);
+    ic.set_dump_on_error(false);
+    let error = ic.progress_program().unwrap_err();
+    assert!(error.ends_with("Invalid jump target -1"), "{}", error);
+}
+
+#[test]
+fn calls_show_up_on_the_call_stack() {
+    let main = assemble(
+        "main",
+        ".import stack, read_int\narb stack\nadd back, 0, [rb+0]\njt 1, read_int\nback: hlt",
+    )
+    .unwrap();
+    let mut ic = Intcode::new(link_with_stdlib(&[main]).unwrap(), true);
+    ic.enable_call_stack();
+    // Stopped in read_int, waiting for input
+    ic.progress_program().unwrap();
+    let call_stack = ic.call_stack().unwrap();
+    let frames = call_stack.frames();
+    assert_eq!(frames.len(), 1);
+    assert_eq!((frames[0].call_site, frames[0].return_addr), (6, 9));
+
+    drop(call_stack);
+    "5\n".bytes().for_each(|c| ic.queue_input(i64::from(c)));
+    ic.progress_program().unwrap();
+    assert_eq!(ic.get_state(), super::IntcodeState::Done);
+    assert!(ic.call_stack().unwrap().frames().is_empty());
+}
@@ -0,0 +1,14 @@
+# alloc(n): returns the address of n fresh cells. The heap grows down from the top of a
+# padded machine's memory, towards the stack growing up from the end of the program, and
+# nothing is ever freed. alloc fails the machine rather than let the heap reach into the
+# program, but it can't see rb, so keeping the heap and the stack apart is up to the caller.
+.import stack
+.export alloc, heap_next
+alloc:      mul [rb+1], -1, [rb+2]
+            add [heap_next], [rb+2], [rb+2]     # the new top of the heap
+            lt [rb+2], stack, [rb+3]
+            jt [rb+3], -1                       # out of memory
+            add [rb+2], 0, [heap_next]
+            add [rb+2], 0, [rb+1]
+            jt 1, [rb+0]
+heap_next:  .data 4096
@@ -0,0 +1,14 @@
+# mac(a, b, n): multiplies the n cells at a by the n cells at b and returns the sum
+.export mac
+mac:        add 0, 0, [rb+4]                # the sum
+loop:       jf [rb+3], done
+            add [rb+1], 0, [multiply+1]
+            add [rb+2], 0, [multiply+2]
+multiply:   mul [0], [0], [rb+5]
+            add [rb+4], [rb+5], [rb+4]
+            add [rb+1], 1, [rb+1]
+            add [rb+2], 1, [rb+2]
+            add [rb+3], -1, [rb+3]
+            jt 1, loop
+done:       add [rb+4], 0, [rb+1]
+            jt 1, [rb+0]
@@ -0,0 +1,11 @@
+# memcpy(dst, src, n): copies n cells from src to dst, first to last
+.export memcpy
+memcpy:     jf [rb+3], done
+            add [rb+2], 0, [copy+1]
+            add [rb+1], 0, [copy+3]
+copy:       add [0], 0, [0]
+            add [rb+1], 1, [rb+1]
+            add [rb+2], 1, [rb+2]
+            add [rb+3], -1, [rb+3]
+            jt 1, memcpy
+done:       jt 1, [rb+0]
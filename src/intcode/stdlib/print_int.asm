@@ -0,0 +1,35 @@
+# print_int(n): outputs n in decimal, as ASCII
+.export print_int
+print_int:
+            lt [rb+1], 0, [rb+4]
+            jf [rb+4], positive
+            out 45                          # '-'
+            jt 1, negative
+positive:   mul [rb+1], -1, [rb+1]          # digits come off a negative n, since -n might not fit
+negative:   add powers, 0, [rb+2]           # the power of ten to count next
+            add 0, 0, [rb+3]                # whether a digit has been printed yet
+next_power: add [rb+2], 0, [load+1]
+load:       add [0], 0, [rb+5]
+            add 0, 0, [rb+6]                # the digit
+            mul [rb+5], -1, [rb+7]
+count:      lt [rb+7], [rb+1], [rb+4]       # until n > -power
+            jt [rb+4], counted
+            add [rb+1], [rb+5], [rb+1]
+            add [rb+6], 1, [rb+6]
+            jt 1, count
+counted:    add [rb+3], [rb+6], [rb+4]      # leading zeros are skipped, except for the last digit
+            eq [rb+5], 1, [rb+7]
+            add [rb+4], [rb+7], [rb+4]
+            jf [rb+4], skip
+            add [rb+6], 48, [rb+4]
+            out [rb+4]
+            add 1, 0, [rb+3]
+skip:       eq [rb+5], 1, [rb+4]
+            jt [rb+4], done
+            add [rb+2], 1, [rb+2]
+            jt 1, next_power
+done:       jt 1, [rb+0]
+powers:     .data 1000000000000000000, 100000000000000000, 10000000000000000
+            .data 1000000000000000, 100000000000000, 10000000000000, 1000000000000
+            .data 100000000000, 10000000000, 1000000000, 100000000, 10000000, 1000000
+            .data 100000, 10000, 1000, 100, 10, 1
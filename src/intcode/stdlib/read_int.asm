@@ -0,0 +1,24 @@
+# read_int(): reads a decimal number from ASCII input, skipping anything in front of it, and
+# consumes the character after it
+.export read_int
+read_int:   add 0, 0, [rb+1]                # the number
+restart:    add 1, 0, [rb+2]                # its sign
+            in [rb+3]
+            eq [rb+3], 45, [rb+4]           # '-'
+            jf [rb+4], first_digit
+            add -1, 0, [rb+2]
+            in [rb+3]
+first_digit: lt [rb+3], 48, [rb+4]
+            jt [rb+4], restart
+            lt [rb+3], 58, [rb+4]
+            jf [rb+4], restart
+digit:      mul [rb+1], 10, [rb+1]
+            add [rb+3], -48, [rb+3]
+            add [rb+1], [rb+3], [rb+1]
+            in [rb+3]
+            lt [rb+3], 48, [rb+4]
+            jt [rb+4], done
+            lt [rb+3], 58, [rb+4]
+            jt [rb+4], digit
+done:       mul [rb+1], [rb+2], [rb+1]
+            jt 1, [rb+0]
@@ -0,0 +1,3 @@
+# Linked last, so the stack grows up from the end of the program
+.export stack
+stack:      .data 0
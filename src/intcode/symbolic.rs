@@ -0,0 +1,460 @@
+use super::instruction::{mnemonic, modes, param_count, Mode};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+
+const MAX_STEPS: usize = 1_000_000;
+
+pub type Env = HashMap<String, i64>;
+
+#[derive(Debug, PartialEq)]
+pub enum Expr {
+    Const(i64),
+    Var(String),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>),
+    // A read through a symbolic address, from memory as it was at the time of the read
+    Select(Rc<Vec<Rc<Expr>>>, Rc<Expr>),
+}
+
+// constant + sum of coefficient * variable
+#[derive(Clone, Debug, PartialEq)]
+struct Linear {
+    constant: i64,
+    terms: BTreeMap<String, i64>,
+}
+
+impl Linear {
+    fn of(expr: &Expr) -> Option<Linear> {
+        match expr {
+            Expr::Const(n) => Some(Linear {
+                constant: *n,
+                terms: BTreeMap::new(),
+            }),
+            Expr::Var(name) => {
+                let mut terms = BTreeMap::new();
+                terms.insert(name.clone(), 1);
+                Some(Linear { constant: 0, terms })
+            }
+            Expr::Add(a, b) => Linear::of(a)?.plus(&Linear::of(b)?),
+            Expr::Mul(a, b) => {
+                let (a, b) = (Linear::of(a)?, Linear::of(b)?);
+                if a.terms.is_empty() {
+                    b.scale(a.constant)
+                } else if b.terms.is_empty() {
+                    a.scale(b.constant)
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // plus and scale give up on overflow, which leaves the expression unfolded
+    fn plus(mut self, other: &Linear) -> Option<Linear> {
+        self.constant = self.constant.checked_add(other.constant)?;
+        for (name, coefficient) in &other.terms {
+            let sum = self.terms.entry(name.clone()).or_insert(0);
+            *sum = sum.checked_add(*coefficient)?;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    fn scale(mut self, factor: i64) -> Option<Linear> {
+        self.constant = self.constant.checked_mul(factor)?;
+        for coefficient in self.terms.values_mut() {
+            *coefficient = coefficient.checked_mul(factor)?;
+        }
+        self.terms.retain(|_, c| *c != 0);
+        Some(self)
+    }
+
+    fn to_expr(&self) -> Rc<Expr> {
+        let mut result: Option<Rc<Expr>> = None;
+        for (name, coefficient) in &self.terms {
+            let var = Rc::new(Expr::Var(name.clone()));
+            let term = if *coefficient == 1 {
+                var
+            } else {
+                Rc::new(Expr::Mul(Rc::new(Expr::Const(*coefficient)), var))
+            };
+            result = Some(match result {
+                Some(sum) => Rc::new(Expr::Add(sum, term)),
+                None => term,
+            });
+        }
+        match result {
+            Some(sum) if self.constant == 0 => sum,
+            Some(sum) => Rc::new(Expr::Add(sum, Rc::new(Expr::Const(self.constant)))),
+            None => Rc::new(Expr::Const(self.constant)),
+        }
+    }
+}
+
+impl Expr {
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    // The constructors fold constants and keep linear expressions in a canonical form so
+    // that long chains of arithmetic stay small.
+    pub fn sum(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        let sum = match (Linear::of(&a), Linear::of(&b)) {
+            (Some(linear_a), Some(linear_b)) => linear_a.plus(&linear_b),
+            _ => None,
+        };
+        match sum {
+            Some(linear) => linear.to_expr(),
+            None => Rc::new(Expr::Add(a, b)),
+        }
+    }
+
+    pub fn product(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        let product = Rc::new(Expr::Mul(a, b));
+        match Linear::of(&product) {
+            Some(linear) => linear.to_expr(),
+            None => product,
+        }
+    }
+
+    pub fn less_than(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.as_const(), b.as_const()) {
+            (Some(a), Some(b)) => Rc::new(Expr::Const((a < b) as i64)),
+            _ => Rc::new(Expr::LessThan(a, b)),
+        }
+    }
+
+    pub fn equals(a: Rc<Expr>, b: Rc<Expr>) -> Rc<Expr> {
+        match (a.as_const(), b.as_const()) {
+            (Some(a), Some(b)) => Rc::new(Expr::Const((a == b) as i64)),
+            _ if a == b => Rc::new(Expr::Const(1)),
+            _ => Rc::new(Expr::Equals(a, b)),
+        }
+    }
+
+    pub fn eval(&self, env: &Env) -> Result<i64, String> {
+        let overflow = || format!("Overflow evaluating {}", self);
+        Ok(match self {
+            Expr::Const(n) => *n,
+            Expr::Var(name) => *env
+                .get(name)
+                .ok_or_else(|| format!("No value for variable {}", name))?,
+            Expr::Add(a, b) => a
+                .eval(env)?
+                .checked_add(b.eval(env)?)
+                .ok_or_else(overflow)?,
+            Expr::Mul(a, b) => a
+                .eval(env)?
+                .checked_mul(b.eval(env)?)
+                .ok_or_else(overflow)?,
+            Expr::LessThan(a, b) => (a.eval(env)? < b.eval(env)?) as i64,
+            Expr::Equals(a, b) => (a.eval(env)? == b.eval(env)?) as i64,
+            Expr::Select(memory, addr) => {
+                let addr = addr.eval(env)?;
+                match memory.get(addr as usize) {
+                    Some(cell) if addr >= 0 => cell.eval(env)?,
+                    _ => return Err(format!("Symbolic read out of bounds: {}", addr)),
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(n) => write!(f, "{}", n),
+            Expr::Var(name) => write!(f, "{}", name),
+            Expr::Add(a, b) => write!(f, "({} + {})", a, b),
+            Expr::Mul(a, b) => write!(f, "{} * {}", a, b),
+            Expr::LessThan(a, b) => write!(f, "({} < {})", a, b),
+            Expr::Equals(a, b) => write!(f, "({} == {})", a, b),
+            Expr::Select(_, addr) => write!(f, "mem[{}]", addr),
+        }
+    }
+}
+
+// Runs a program where some cells and every input are unknowns. Control flow, opcodes and
+// write addresses have to stay concrete, reads through a symbolic address are allowed.
+// Memory is shared with the Select expressions that read it, and only copied when it's
+// written to while one of them still holds it.
+pub struct SymbolicIntcode {
+    memory: Rc<Vec<Rc<Expr>>>,
+    ip: usize,
+    rb: i64,
+    inputs: usize,
+    outputs: Vec<Rc<Expr>>,
+}
+
+impl SymbolicIntcode {
+    pub fn new(program: &[i64]) -> Self {
+        SymbolicIntcode {
+            memory: Rc::new(program.iter().map(|n| Rc::new(Expr::Const(*n))).collect()),
+            ip: 0,
+            rb: 0,
+            inputs: 0,
+            outputs: Vec::new(),
+        }
+    }
+
+    pub fn symbolize(&mut self, addr: usize, name: &str) {
+        Rc::make_mut(&mut self.memory)[addr] = Rc::new(Expr::Var(name.to_string()));
+    }
+
+    pub fn memory_at(&self, addr: usize) -> &Rc<Expr> {
+        &self.memory[addr]
+    }
+
+    pub fn outputs(&self) -> &[Rc<Expr>] {
+        &self.outputs
+    }
+
+    fn cell(&self, addr: i64) -> Result<Rc<Expr>, String> {
+        if addr < 0 || addr as usize >= self.memory.len() {
+            return Err(format!("Address out of bounds at {}: {}", self.ip, addr));
+        }
+        Ok(Rc::clone(&self.memory[addr as usize]))
+    }
+
+    fn concrete(&self, expr: &Expr, what: &str) -> Result<i64, String> {
+        expr.as_const()
+            .ok_or_else(|| format!("Symbolic {} at {}: {}", what, self.ip, expr))
+    }
+
+    fn offset(&self, base: i64, offset: i64) -> Result<i64, String> {
+        base.checked_add(offset)
+            .ok_or_else(|| format!("Address overflows at {}: {} from {}", self.ip, offset, base))
+    }
+
+    fn read_param(&self, offset: usize, mode: Mode) -> Result<Rc<Expr>, String> {
+        let param = self.cell((self.ip + offset) as i64)?;
+        let base = match mode {
+            Mode::Position => 0,
+            Mode::Immediate => return Ok(param),
+            Mode::Relative => self.rb,
+        };
+        match param.as_const() {
+            Some(addr) => self.cell(self.offset(base, addr)?),
+            None => {
+                let addr = Expr::sum(param, Rc::new(Expr::Const(base)));
+                Ok(Rc::new(Expr::Select(Rc::clone(&self.memory), addr)))
+            }
+        }
+    }
+
+    fn write_param(&mut self, offset: usize, mode: Mode, val: Rc<Expr>) -> Result<(), String> {
+        let param = self.cell((self.ip + offset) as i64)?;
+        let addr = self.concrete(&param, "write address")?;
+        let addr = match mode {
+            Mode::Position => addr,
+            Mode::Relative => self.offset(self.rb, addr)?,
+            Mode::Immediate => return Err(format!("Immediate mode write at {}", self.ip)),
+        };
+        self.cell(addr)?;
+        Rc::make_mut(&mut self.memory)[addr as usize] = val;
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), String> {
+        for _ in 0..MAX_STEPS {
+            let opcode = self.cell(self.ip as i64)?;
+            let opcode = self.concrete(&opcode, "opcode")?;
+            let op = opcode % 100;
+            if opcode < 0 || mnemonic(op).is_none() {
+                return Err(format!("Unsupported opcode: {}", opcode));
+            }
+            let modes = modes(opcode).map_err(|e| format!("{} at {}", e, self.ip))?;
+            let mode = |n: usize| modes[n - 1];
+            match op {
+                1 | 2 | 7 | 8 => {
+                    let a = self.read_param(1, mode(1))?;
+                    let b = self.read_param(2, mode(2))?;
+                    let result = match op {
+                        1 => Expr::sum(a, b),
+                        2 => Expr::product(a, b),
+                        7 => Expr::less_than(a, b),
+                        _ => Expr::equals(a, b),
+                    };
+                    self.write_param(3, mode(3), result)?;
+                }
+                3 => {
+                    let input = Rc::new(Expr::Var(format!("input{}", self.inputs)));
+                    self.inputs += 1;
+                    self.write_param(1, mode(1), input)?;
+                }
+                4 => {
+                    let output = self.read_param(1, mode(1))?;
+                    self.outputs.push(output);
+                }
+                5 | 6 => {
+                    let cond = self.read_param(1, mode(1))?;
+                    let cond = self.concrete(&cond, "branch condition")?;
+                    let target = self.read_param(2, mode(2))?;
+                    let target = self.concrete(&target, "jump target")?;
+                    if (cond != 0) == (op == 5) {
+                        self.ip = target as usize;
+                        continue;
+                    }
+                }
+                9 => {
+                    let offset = self.read_param(1, mode(1))?;
+                    let offset = self.concrete(&offset, "relative base offset")?;
+                    self.rb = self.offset(self.rb, offset)?;
+                }
+                // Only hlt is left
+                _ => return Ok(()),
+            }
+            self.ip += param_count(op) + 1;
+        }
+        Err(format!("Gave up after {} steps", MAX_STEPS))
+    }
+}
+
+// Every assignment of the variables within their domains that makes expr equal target.
+// Linear expressions are solved for their last variable directly, anything else is
+// evaluated over the whole domain.
+pub fn solve(
+    expr: &Expr,
+    target: i64,
+    domains: &[(&str, RangeInclusive<i64>)],
+) -> Result<Vec<Env>, String> {
+    let linear = Linear::of(expr);
+    let solved = match &linear {
+        Some(linear) => domains
+            .iter()
+            .rposition(|(name, _)| linear.terms.contains_key(*name)),
+        None => None,
+    };
+
+    let mut solutions = Vec::new();
+    let mut env: Env = HashMap::new();
+    let free: Vec<&(&str, RangeInclusive<i64>)> = domains
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| Some(*i) != solved)
+        .map(|(_, domain)| domain)
+        .collect();
+
+    fn assign(
+        free: &[&(&str, RangeInclusive<i64>)],
+        env: &mut Env,
+        visit: &mut dyn FnMut(&mut Env) -> Result<(), String>,
+    ) -> Result<(), String> {
+        match free.split_first() {
+            None => visit(env),
+            Some(((name, range), rest)) => {
+                for value in range.clone() {
+                    env.insert(name.to_string(), value);
+                    assign(rest, env, visit)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    assign(&free, &mut env, &mut |env| {
+        match (&linear, solved) {
+            (Some(linear), Some(i)) => {
+                let (name, range) = &domains[i];
+                let coefficient = linear.terms[*name];
+                // Overflowing along the way counts as no solution
+                let mut rest = target.checked_sub(linear.constant);
+                for (other, c) in &linear.terms {
+                    if other != name {
+                        let value = env
+                            .get(other)
+                            .ok_or_else(|| format!("No domain given for variable {}", other))?;
+                        rest = rest.and_then(|rest| rest.checked_sub(c.checked_mul(*value)?));
+                    }
+                }
+                let value = rest
+                    .filter(|rest| rest.checked_rem(coefficient) == Some(0))
+                    .and_then(|rest| rest.checked_div(coefficient));
+                if let Some(value) = value.filter(|value| range.contains(value)) {
+                    let mut solution = env.clone();
+                    solution.insert(name.to_string(), value);
+                    solutions.push(solution);
+                }
+            }
+            _ => {
+                if expr.eval(env)? == target {
+                    solutions.push(env.clone());
+                }
+            }
+        }
+        Ok(())
+    })?;
+    Ok(solutions)
+}
+
+#[cfg(test)]
+fn solution(pairs: &[(&str, i64)]) -> Env {
+    pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+}
+
+#[test]
+fn can_build_linear_expressions_for_memory() {
+    // mem[0] = (a + b) * 3 where a and b are patched in as immediates
+    let mut sym = SymbolicIntcode::new(&[1101, 0, 0, 0, 1002, 0, 3, 0, 99]);
+    sym.symbolize(1, "a");
+    sym.symbolize(2, "b");
+    sym.run().unwrap();
+    assert_eq!(sym.memory_at(0).to_string(), "(3 * a + 3 * b)");
+
+    let solutions = solve(sym.memory_at(0), 30, &[("a", 0..=9), ("b", 0..=9)]).unwrap();
+    let expected: Vec<_> = (1..=9)
+        .map(|a| solution(&[("a", a), ("b", 10 - a)]))
+        .collect();
+    assert_eq!(solutions, expected);
+}
+
+#[test]
+fn can_follow_inputs_and_symbolic_reads() {
+    let mut sym = SymbolicIntcode::new(&[3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+    sym.run().unwrap();
+    assert_eq!(sym.outputs()[0].to_string(), "2 * input0");
+
+    // The input becomes the address of the following out instruction
+    sym = SymbolicIntcode::new(&[3, 3, 4, 0, 99, 7, 99]);
+    sym.run().unwrap();
+    assert_eq!(sym.outputs()[0].to_string(), "mem[input0]");
+    let solutions = solve(&sym.outputs()[0], 99, &[("input0", 0..=6)]).unwrap();
+    assert_eq!(
+        solutions,
+        vec![solution(&[("input0", 4)]), solution(&[("input0", 6)])]
+    );
+}
+
+#[test]
+fn symbolic_branches_are_rejected() {
+    let mut sym = SymbolicIntcode::new(&[3, 5, 1005, 5, 0, 0, 99]);
+    assert_eq!(
+        sym.run(),
+        Err("Symbolic branch condition at 2: input0".to_string())
+    );
+}
+
+#[test]
+fn overflow_is_left_unfolded_and_fails_evaluation() {
+    let sum = Expr::sum(Rc::new(Expr::Const(i64::MAX)), Rc::new(Expr::Const(1)));
+    assert_eq!(sum.to_string(), "(9223372036854775807 + 1)");
+    assert_eq!(
+        sum.eval(&Env::new()),
+        Err("Overflow evaluating (9223372036854775807 + 1)".to_string())
+    );
+
+    // mem[0] = a + i64::MAX
+    let mut sym = SymbolicIntcode::new(&[1101, 0, i64::MAX, 0, 99]);
+    sym.symbolize(1, "a");
+    sym.run().unwrap();
+    let solutions = solve(sym.memory_at(0), i64::MIN, &[("a", -2..=2)]).unwrap();
+    assert_eq!(solutions, vec![]);
+}
@@ -0,0 +1,98 @@
+use super::instruction::param_count;
+use super::{Intcode, Observer};
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub ip: usize,
+    pub words: Vec<i64>,
+}
+
+// Keeps the last `capacity` instructions a machine executed
+#[derive(Clone)]
+pub struct Trace {
+    capacity: usize,
+    entries: VecDeque<TraceEntry>,
+    total: u64,
+}
+
+impl Trace {
+    pub fn new(capacity: usize) -> Self {
+        Trace {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+            total: 0,
+        }
+    }
+
+    pub fn record(&mut self, ip: usize, words: &[i64]) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TraceEntry {
+            ip,
+            words: words.to_vec(),
+        });
+        self.total += 1;
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &TraceEntry> {
+        self.entries.iter()
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl Observer for Trace {
+    fn before_instruction(&mut self, ic: &Intcode, ip: usize) {
+        let end = (ip + param_count(ic.memory[ip] % 100) + 1).min(ic.memory.len());
+        self.record(ip, &ic.memory.range(ip..end));
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let skipped = self.total - self.entries.len() as u64;
+        if skipped > 0 {
+            writeln!(f, "    ... {} earlier instructions", skipped)?;
+        }
+        for entry in &self.entries {
+            let words: Vec<String> = entry.words.iter().map(|w| w.to_string()).collect();
+            writeln!(f, "    {:>6}: {}", entry.ip, words.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_keeps_the_most_recent_instructions() {
+    let mut ic = Intcode::new(vec![1101, 1, 1, 5, 104, 0, 99], false);
+    let trace = std::rc::Rc::new(std::cell::RefCell::new(Trace::new(2)));
+    ic.add_observer(trace.clone());
+    ic.progress_program().unwrap();
+    let trace = trace.borrow();
+    assert_eq!(trace.total(), 3);
+    assert_eq!(
+        trace.entries().cloned().collect::<Vec<_>>(),
+        vec![
+            TraceEntry {
+                ip: 4,
+                words: vec![104, 2]
+            },
+            TraceEntry {
+                ip: 6,
+                words: vec![99]
+            }
+        ]
+    );
+    assert_eq!(
+        trace.to_string(),
+        "    ... 1 earlier instructions\n         4: 104,2\n         6: 99\n"
+    );
+}
@@ -0,0 +1,175 @@
+use super::instruction::{modes, Mode};
+use super::{Instruction, Intcode};
+use std::collections::BTreeSet;
+
+// Which parameter an instruction writes to, counting from 1
+fn written_param(op: i64) -> Option<usize> {
+    match op {
+        1 | 2 | 7 | 8 => Some(3),
+        3 => Some(1),
+        _ => None,
+    }
+}
+
+// Checks the parameter modes of a built-in instruction. Any mode other than 0, 1 or 2, a
+// mode for a parameter the instruction doesn't have, or an immediate mode write is an error.
+pub fn check_modes(opcode: i64) -> Result<(), String> {
+    let modes = modes(opcode)?;
+    match written_param(opcode % 100) {
+        Some(param) if modes[param - 1] == Mode::Immediate => Err(format!(
+            "Immediate mode write to parameter {} of {}",
+            param, opcode
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Issue {
+    pub addr: usize,
+    pub message: String,
+}
+
+// The address of every instruction reachable from address 0, found before the program runs.
+// Jumps are followed when their target is immediate, and the instruction after an
+// unconditional jump is only followed when its address shows up as an immediate somewhere
+// (which is how return addresses get saved), so data after the code isn't mistaken for
+// instructions. Modes are read the way the machine reads them (see Mode::lenient), and the
+// walk stops at anything that doesn't decode as a built-in instruction.
+pub fn reachable(program: &[i64]) -> Vec<usize> {
+    let mut instructions = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut immediates = BTreeSet::new();
+    let mut return_sites = Vec::new();
+    let mut pending = vec![0];
+
+    loop {
+        while let Some(addr) = pending.pop() {
+            if !visited.insert(addr) {
+                continue;
+            }
+            let instruction = match Instruction::decode_lenient(program, addr) {
+                Ok(instruction) => instruction,
+                Err(_) => continue,
+            };
+            instructions.push(addr);
+
+            for param in instruction.params() {
+                if param.mode == Mode::Immediate {
+                    immediates.insert(param.value);
+                }
+            }
+
+            let next = addr + instruction.size();
+            match instruction {
+                Instruction::JumpIfTrue(_, target) | Instruction::JumpIfFalse(_, target) => {
+                    if target.mode == Mode::Immediate && target.value >= 0 {
+                        pending.push(target.value as usize);
+                    }
+                    if instruction.taken() == Some(true) {
+                        return_sites.push(next);
+                    } else {
+                        pending.push(next);
+                    }
+                }
+                Instruction::Halt => {}
+                _ => pending.push(next),
+            }
+        }
+
+        let before = return_sites.len();
+        return_sites.retain(|site| {
+            if immediates.contains(&(*site as i64)) {
+                pending.push(*site);
+                false
+            } else {
+                true
+            }
+        });
+        if return_sites.len() == before {
+            break;
+        }
+    }
+
+    instructions.sort_unstable();
+    instructions
+}
+
+// Reports every reachable instruction strict mode would reject
+pub fn validate(program: &[i64]) -> Vec<Issue> {
+    reachable(program)
+        .into_iter()
+        .filter_map(|addr| {
+            check_modes(program[addr])
+                .err()
+                .map(|message| Issue { addr, message })
+        })
+        .collect()
+}
+
+impl Intcode {
+    // In strict mode every built-in instruction has its modes checked before it executes,
+    // instead of unknown modes acting as immediate and immediate writes being dropped
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+}
+
+#[test]
+fn strict_mode_rejects_bad_parameter_modes() {
+    assert_eq!(check_modes(21101), Ok(()));
+    assert_eq!(
+        check_modes(11101),
+        Err("Immediate mode write to parameter 3 of 11101".to_string())
+    );
+    assert_eq!(
+        check_modes(1302),
+        Err("Invalid mode 3 for parameter 1 of 1302".to_string())
+    );
+    assert_eq!(
+        check_modes(199),
+        Err("Too many parameter modes in 199".to_string())
+    );
+
+    // add 1, 2, 5 silently does nothing unless the machine is strict
+    let program = vec![11101, 1, 2, 5, 99, 0];
+    let mut ic = Intcode::new(program.clone(), false);
+    ic.progress_program().unwrap();
+    let mut ic = Intcode::new(program, false);
+    ic.set_strict(true);
+    assert_eq!(
+        ic.progress_program(),
+        Err("Invalid instruction at 0: Immediate mode write to parameter 3 of 11101".to_string())
+    );
+}
+
+#[test]
+fn validator_finds_bad_instructions_in_reachable_code() {
+    for day in &["02", "05", "09"] {
+        let program = super::loader::load_program(&format!("res/day_{}.txt", day)).unwrap();
+        assert_eq!(validate(&program), vec![], "day {}", day);
+    }
+
+    #[rustfmt::skip]
+    let program = vec![
+        21101, 0, 7, 0,      //  0: add 0, 7, [rb+0]    save the return address
+        1105, 1, 9,          //  4: jt 1, 9             call
+        1199,                //  7: only reached by returning
+        3301,                //  8: data, never reached
+        11101, 1, 2, 13,     //  9: add 1, 2, 13
+        2105, 1, 0,          // 13: jt 1, [rb+0]        return
+    ];
+    assert_eq!(
+        validate(&program),
+        vec![
+            Issue {
+                addr: 7,
+                message: "Too many parameter modes in 1199".to_string()
+            },
+            Issue {
+                addr: 9,
+                message: "Immediate mode write to parameter 3 of 11101".to_string()
+            }
+        ]
+    );
+}
@@ -0,0 +1,13 @@
+#![warn(clippy::all)]
+
+pub mod day_01;
+pub mod day_02;
+pub mod day_03;
+pub mod day_04;
+pub mod day_05;
+pub mod day_06;
+pub mod day_07;
+pub mod day_08;
+pub mod day_09;
+pub mod intcode;
+pub mod linear_algebra;
@@ -1,19 +1,8 @@
 #![warn(clippy::all)]
 
+use aoc2019_rust::*;
 use colored::*;
 
-mod day_01;
-mod day_02;
-mod day_03;
-mod day_04;
-mod day_05;
-mod day_06;
-mod day_07;
-mod day_08;
-mod day_09;
-mod intcode;
-mod linear_algebra;
-
 macro_rules! solve {
     ($title: literal, $module:tt) => {
         println!("{}", $title.green().bold().underline());